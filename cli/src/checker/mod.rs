@@ -0,0 +1,687 @@
+use std::{collections::HashMap, fs, rc::Rc};
+
+use lib::{
+    error::{Error, Result},
+    instruction::{Argument, Instruction, ObjectEntry, Reporter},
+    span::{Chunk, Span},
+    type_error,
+    value::MapRef,
+};
+use ty::{Parameter, ParameterKind, Signature, Type};
+
+use crate::{compile, engine::Engine, std_lib::prelude::Prelude};
+
+#[cfg(test)]
+mod tests;
+pub mod ty;
+
+/// Statically checks modules against their type annotations. Values without an
+/// annotation are inferred where possible, and otherwise have the dynamic type `*`,
+/// which is never an error.
+#[derive(Default)]
+pub struct Checker {
+    /// Provides the prelude and native modules.
+    engine: Engine,
+    modules: HashMap<String, Type>,
+    pub reports: Vec<Report>,
+}
+
+/// The problems found in one module.
+pub struct Report {
+    pub path: String,
+    pub source: String,
+    pub errors: Vec<Error>,
+}
+
+struct Binding {
+    ty: Type,
+    /// Annotated variables keep their type when they are assigned to again.
+    declared: bool,
+}
+
+/// Walks the instructions of one module, keeping a frame of bindings for each scope.
+/// Frames line up with the scopes the compiler resolved, so `Parent(n)` is the n-th
+/// frame from the top.
+struct Walker<'c> {
+    checker: &'c mut Checker,
+    frames: Vec<HashMap<String, Binding>>,
+    errors: Vec<Error>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks a module and every module it imports, returning the type of the module.
+    pub fn check_file(&mut self, path: &str, span: Span) -> Result<Type> {
+        if let Some(ty) = self.modules.get(path) {
+            return Ok(ty.clone());
+        }
+        // Modules that import each other see each other as dynamic.
+        self.modules.insert(path.to_string(), Type::Any);
+
+        let source = fs::read_to_string(path)
+            .map_err(|_| Error::new(format!("The module path '{path}' does not exist."), span))?;
+        let (ty, errors) = self.check_source(&source);
+        self.reports.push(Report {
+            path: path.to_string(),
+            source,
+            errors,
+        });
+        self.modules.insert(path.to_string(), ty.clone());
+        Ok(ty)
+    }
+
+    fn check_source(&mut self, source: &str) -> (Type, Vec<Error>) {
+        let program = match compile(source) {
+            Ok(program) => program,
+            Err(e) => return (Type::Any, vec![e]),
+        };
+
+        let values = self.engine.prelude().borrow();
+        let prelude = Prelude::keys()
+            .into_iter()
+            .filter_map(|key| {
+                let ty = Type::from_value(values.get(key)?);
+                Some((
+                    key.to_string(),
+                    Binding {
+                        ty,
+                        declared: false,
+                    },
+                ))
+            })
+            .collect();
+        drop(values);
+        let mut walker = Walker {
+            checker: self,
+            frames: vec![prelude],
+            errors: Vec::new(),
+        };
+        let ty = walker.block(HashMap::new(), &program.data);
+        (ty, walker.errors)
+    }
+}
+
+impl Walker<'_> {
+    fn error(&mut self, message: impl Into<String>, span: Span) {
+        self.errors.push(Error::new(message, span));
+    }
+
+    /// Checks `reporter`, reporting an error if its type isn't accepted by `expected`.
+    fn expect(&mut self, expected: &Type, reporter: &Reporter, span: Span) -> Type {
+        let ty = self.reporter(reporter, span);
+        if !expected.accepts(&ty) {
+            self.error(type_error!(expected, ty), span);
+        }
+        ty
+    }
+
+    /// Checks an operand of a numeric operator.
+    fn number(&mut self, reporter: &Reporter, span: Span) -> Type {
+        match self.reporter(reporter, span) {
+            ty @ (Type::Int | Type::Float | Type::Any) => ty,
+            ty => {
+                self.error(type_error!("number", ty), span);
+                Type::Any
+            }
+        }
+    }
+
+    /// Resolves an annotation to a type. Besides the built in names, record types that
+    /// are in scope can be used as annotations.
+    fn annotation(&mut self, name: &Chunk<&str>) -> Type {
+        if let Some(ty) = Type::from_name(name.data) {
+            return ty;
+        }
+        let record = self
+            .frames
+            .iter()
+            .rev()
+            .find_map(|frame| frame.get(name.data))
+            .and_then(|binding| match &binding.ty {
+                Type::Function(Some(signature)) => match &signature.returns {
+                    ty @ Type::Object {
+                        name: Some(record), ..
+                    } if record == name.data => Some(ty.clone()),
+                    _ => None,
+                },
+                _ => None,
+            });
+        record.unwrap_or_else(|| {
+            self.error(format!("Unknown type '{}'.", name.data), name.span);
+            Type::Any
+        })
+    }
+
+    fn frame(&mut self, up: usize) -> Option<&mut HashMap<String, Binding>> {
+        let index = self.frames.len().checked_sub(up + 1)?;
+        self.frames.get_mut(index)
+    }
+
+    /// Checks a block in a new frame. Like the interpreter, a block evaluates to the value
+    /// of its first `return`, or to its scope if it doesn't return.
+    fn block(&mut self, frame: HashMap<String, Binding>, body: &[Chunk<Instruction>]) -> Type {
+        self.frames.push(frame);
+        let mut returns = None;
+        for instruction in body {
+            if let Some(ty) = self.instruction(instruction) {
+                returns = Some(ty);
+                break;
+            }
+        }
+        let frame = self.frames.pop().unwrap_or_default();
+        returns.unwrap_or_else(|| Type::Object {
+            name: None,
+            fields: Rc::new(
+                frame
+                    .into_iter()
+                    .map(|(name, binding)| (name, binding.ty))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Returns the type of the returned value for `return` instructions.
+    fn instruction(&mut self, instruction: &Chunk<Instruction>) -> Option<Type> {
+        match &instruction.data {
+            Instruction::Set {
+                map,
+                name,
+                value,
+                ty,
+            } => {
+                let value_ty = self.reporter(&value.data, value.span);
+                let Reporter::Parent(up) = map.data else {
+                    let map_ty = self.reporter(&map.data, map.span);
+                    self.set_field(&map_ty, name, &value_ty, value.span);
+                    return None;
+                };
+
+                let annotation = ty.as_ref().map(|ty| self.annotation(ty));
+                let existing = self
+                    .frame(up)
+                    .and_then(|frame| frame.get(name.data))
+                    .map(|binding| (binding.ty.clone(), binding.declared));
+                let binding = match (annotation, existing) {
+                    (Some(declared), _) | (None, Some((declared, true))) => {
+                        if !declared.accepts(&value_ty) {
+                            self.error(type_error!(declared, value_ty), value.span);
+                        }
+                        Binding {
+                            ty: declared,
+                            declared: true,
+                        }
+                    }
+                    (None, Some((ty, false))) => Binding {
+                        ty: ty.join(&value_ty),
+                        declared: false,
+                    },
+                    // Variables that start out as null are usually filled in later.
+                    (None, None) => Binding {
+                        ty: match value_ty {
+                            Type::Null => Type::Any,
+                            ty => ty,
+                        },
+                        declared: false,
+                    },
+                };
+                if let Some(frame) = self.frame(up) {
+                    frame.insert(name.data.to_string(), binding);
+                }
+                None
+            }
+            Instruction::While { condition, body } => {
+                self.expect(&Type::Bool, &condition.data, condition.span);
+                self.block(HashMap::new(), &body.data);
+                None
+            }
+            Instruction::For { name, iter, body } => {
+                let item = match self.reporter(&iter.data, iter.span) {
+                    Type::Array(item) => *item,
+                    Type::Any | Type::Function(_) => Type::Any,
+                    ty => {
+                        self.error(
+                            format!("Expected an iterator function, but instead got {ty}"),
+                            iter.span,
+                        );
+                        Type::Any
+                    }
+                };
+                let frame = HashMap::from([(
+                    name.data.to_string(),
+                    Binding {
+                        ty: item,
+                        declared: false,
+                    },
+                )]);
+                self.block(frame, &body.data);
+                None
+            }
+            Instruction::Return(value) => Some(self.reporter(&value.data, value.span)),
+            Instruction::Void(reporter) => {
+                self.reporter(reporter, instruction.span);
+                None
+            }
+        }
+    }
+
+    fn set_field(&mut self, map: &Type, name: &Chunk<&str>, value: &Type, span: Span) {
+        match map {
+            Type::Object {
+                name: Some(record),
+                fields,
+            } => match fields.get(name.data) {
+                Some(field) if !field.accepts(value) => self.error(type_error!(field, value), span),
+                Some(_) => (),
+                None => self.error(
+                    format!("The type {record} has no field '{}'.", name.data),
+                    name.span,
+                ),
+            },
+            Type::Any | Type::Map | Type::Object { .. } | Type::Module(_) => (),
+            ty => self.error(type_error!("map", ty), name.span),
+        }
+    }
+
+    fn property(&mut self, map: Type, name: &Chunk<&str>, optional: bool, span: Span) -> Type {
+        match map {
+            Type::Object {
+                name: Some(record),
+                fields,
+            } => fields.get(name.data).cloned().unwrap_or_else(|| {
+                if !optional {
+                    self.error(
+                        format!("The type {record} has no field '{}'.", name.data),
+                        name.span,
+                    );
+                }
+                Type::Any
+            }),
+            // Scopes also look up their parents, so a missing field isn't an error.
+            Type::Object { fields, .. } => fields.get(name.data).cloned().unwrap_or(Type::Any),
+            Type::Module(map) => match map.borrow().get(name.data) {
+                Some(value) => Type::from_value(value),
+                None => {
+                    if !optional {
+                        self.error(format!("No property '{}'.", name.data), name.span);
+                    }
+                    Type::Any
+                }
+            },
+            Type::Null if optional => Type::Null,
+            Type::Any | Type::Map => Type::Any,
+            ty => {
+                self.error(type_error!("map", ty), span);
+                Type::Any
+            }
+        }
+    }
+
+    fn function(
+        &mut self,
+        parameters: &[lib::instruction::Parameter],
+        returns: Option<&Chunk<&str>>,
+        body: &Chunk<Box<Reporter>>,
+    ) -> Type {
+        self.frames.push(HashMap::new());
+        let mut signature = Vec::new();
+        for parameter in parameters {
+            let ty = parameter
+                .ty
+                .as_ref()
+                .map_or(Type::Any, |ty| self.annotation(ty));
+            if let Some(default) = &parameter.default {
+                self.expect(&ty, &default.data, default.span);
+            }
+            let (kind, local) = if parameter.rest {
+                (ParameterKind::Rest, Type::Array(Box::new(ty.clone())))
+            } else if parameter.default.is_some() {
+                (ParameterKind::Optional, ty.clone())
+            } else {
+                (ParameterKind::Required, ty.clone())
+            };
+            if let Some(frame) = self.frame(0) {
+                frame.insert(
+                    parameter.name.data.to_string(),
+                    Binding {
+                        ty: local,
+                        declared: parameter.ty.is_some(),
+                    },
+                );
+            }
+            signature.push(Parameter {
+                name: Some(parameter.name.data.to_string()),
+                ty,
+                kind,
+            });
+        }
+
+        let body_ty = self.reporter(&body.data, body.span);
+        self.frames.pop();
+        let returns = match returns {
+            Some(returns) => {
+                let declared = self.annotation(returns);
+                if !declared.accepts(&body_ty) {
+                    self.error(
+                        format!(
+                            "This function should return {declared}, but it returns {body_ty}."
+                        ),
+                        body.span,
+                    );
+                }
+                declared
+            }
+            None => body_ty,
+        };
+        Type::Function(Some(Rc::new(Signature {
+            parameters: signature,
+            returns,
+        })))
+    }
+
+    fn call(
+        &mut self,
+        function: &Chunk<Box<Reporter>>,
+        args: &[Chunk<Argument>],
+        span: Span,
+    ) -> Type {
+        let signature = match self.reporter(&function.data, function.span) {
+            Type::Function(signature) => signature,
+            Type::Any => None,
+            ty => {
+                self.error(type_error!("function", ty), function.span);
+                None
+            }
+        };
+
+        let mut positional = Vec::new();
+        let mut named = Vec::new();
+        let mut spread = false;
+        for arg in args {
+            match &arg.data {
+                Argument::Positional(value) => {
+                    let ty = self.reporter(value, arg.span);
+                    positional.push((ty, arg.span));
+                }
+                Argument::Spread(value) => {
+                    self.reporter(value, arg.span);
+                    spread = true;
+                }
+                Argument::Named(name, value) => {
+                    named.push((*name, self.reporter(&value.data, value.span), value.span))
+                }
+            }
+        }
+
+        let Some(signature) = signature else {
+            return Type::Any;
+        };
+        // The number of arguments given by a spread isn't known.
+        if spread {
+            return signature.returns.clone();
+        }
+
+        let rest = signature
+            .parameters
+            .iter()
+            .find(|p| p.kind == ParameterKind::Rest);
+        let fixed =
+            &signature.parameters[..signature.parameters.len() - usize::from(rest.is_some())];
+        for (i, (ty, span)) in positional.iter().enumerate() {
+            let expected = match (fixed.get(i), rest) {
+                (Some(parameter), _) | (None, Some(parameter)) => &parameter.ty,
+                (None, None) => break,
+            };
+            if !expected.accepts(ty) {
+                self.error(type_error!(expected, ty), *span);
+            }
+        }
+
+        let mut given = vec![false; fixed.len()];
+        given
+            .iter_mut()
+            .take(positional.len())
+            .for_each(|g| *g = true);
+        for (name, ty, span) in &named {
+            match fixed
+                .iter()
+                .position(|p| p.name.as_deref() == Some(name.data))
+            {
+                Some(index) => {
+                    given[index] = true;
+                    if !fixed[index].ty.accepts(ty) {
+                        self.error(type_error!(fixed[index].ty, ty), *span);
+                    }
+                }
+                None => self.error(
+                    format!("This function has no parameter named '{}'.", name.data),
+                    name.span,
+                ),
+            }
+        }
+
+        let required = fixed
+            .iter()
+            .filter(|p| p.kind == ParameterKind::Required)
+            .count();
+        let count = positional.len() + named.len();
+        let missing = fixed
+            .iter()
+            .zip(&given)
+            .any(|(p, given)| p.kind == ParameterKind::Required && !given);
+        if missing || (rest.is_none() && positional.len() > fixed.len()) {
+            let expected = match (rest, required == fixed.len()) {
+                (Some(_), _) => format!("at least {required} argument"),
+                (None, true) => format!("{required} argument"),
+                (None, false) => format!("{required} to {} argument", fixed.len()),
+            };
+            let plural = if required == 1 && fixed.len() == 1 {
+                ""
+            } else {
+                "s"
+            };
+            self.error(
+                format!("This function expects {expected}{plural}, but got {count}."),
+                span,
+            );
+        }
+
+        signature.returns.clone()
+    }
+
+    fn import(&mut self, path: &Chunk<&str>) -> Type {
+        if let Some(module) = self.checker.engine.module(path.data) {
+            return Type::from_value(&module);
+        }
+        match self.checker.check_file(path.data, path.span) {
+            Ok(ty) => ty,
+            Err(e) => {
+                self.errors.push(e);
+                Type::Any
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, a: &Chunk<Box<Reporter>>, b: &Chunk<Box<Reporter>>) -> Type {
+        let a_ty = self.number(&a.data, a.span);
+        let b_ty = self.number(&b.data, b.span);
+        match (a_ty, b_ty) {
+            (Type::Int, Type::Float) | (Type::Float, Type::Int) => {
+                self.error("You cannot do arithmetic with floats and integers.", b.span);
+                Type::Any
+            }
+            (Type::Any, ty) | (ty, _) => ty,
+        }
+    }
+
+    fn reporter(&mut self, reporter: &Reporter, span: Span) -> Type {
+        match reporter {
+            Reporter::Parent(_) => Type::Map,
+            Reporter::Null => Type::Null,
+            Reporter::ConstStr(_) => Type::String,
+            Reporter::ConstInt(_) => Type::Int,
+            Reporter::ConstFloat(_) => Type::Float,
+            Reporter::ConstBool(_) => Type::Bool,
+
+            Reporter::Block(body) => self.block(HashMap::new(), body),
+            Reporter::Array(items) => Type::Array(Box::new(
+                items
+                    .data
+                    .iter()
+                    .map(|item| self.reporter(&item.data, item.span))
+                    .reduce(|a, b| a.join(&b))
+                    .unwrap_or(Type::Any),
+            )),
+            Reporter::Object(entries) => {
+                let mut fields = Some(HashMap::new());
+                for entry in entries {
+                    match &entry.data {
+                        ObjectEntry::Field(key, value) => {
+                            let ty = self.reporter(&value.data, value.span);
+                            if let Some(fields) = &mut fields {
+                                fields.insert(key.data.to_string(), ty);
+                            }
+                        }
+                        ObjectEntry::Computed(key, value) => {
+                            self.expect(&Type::String, &key.data, key.span);
+                            self.reporter(&value.data, value.span);
+                            fields = None;
+                        }
+                        ObjectEntry::Spread(value) => {
+                            let span = entry.span;
+                            let ty = self.reporter(value, span);
+                            if !Type::Map.accepts(&ty) {
+                                self.error(type_error!("map", ty), span);
+                            }
+                            fields = None;
+                        }
+                    }
+                }
+                match fields {
+                    Some(fields) => Type::Object {
+                        name: None,
+                        fields: Rc::new(fields.into_iter().collect()),
+                    },
+                    None => Type::Map,
+                }
+            }
+            Reporter::Function {
+                parameters,
+                returns,
+                body,
+            } => self.function(parameters, returns.as_ref(), body),
+            Reporter::RecordType { name, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, ty)| (field.data.to_string(), self.annotation(ty)))
+                    .collect::<Vec<_>>();
+                Type::Function(Some(Rc::new(Signature {
+                    parameters: fields
+                        .iter()
+                        .map(|(field, ty)| Parameter {
+                            name: Some(field.clone()),
+                            ty: ty.clone(),
+                            kind: ParameterKind::Required,
+                        })
+                        .collect(),
+                    returns: Type::Object {
+                        name: Some(name.data.to_string()),
+                        fields: Rc::new(fields.into_iter().collect()),
+                    },
+                })))
+            }
+
+            Reporter::Get {
+                map,
+                name,
+                optional,
+            } => {
+                let map_ty = match map.data.as_ref() {
+                    Reporter::Parent(up) => {
+                        return self
+                            .frame(*up)
+                            .and_then(|frame| frame.get(name.data))
+                            .map_or(Type::Any, |binding| binding.ty.clone());
+                    }
+                    _ => self.reporter(&map.data, map.span),
+                };
+                self.property(map_ty, name, *optional, map.span)
+            }
+            Reporter::DynGet { map, attr, .. } => {
+                self.reporter(&map.data, map.span);
+                self.expect(&Type::String, &attr.data, attr.span);
+                Type::Any
+            }
+            Reporter::Call(function, args) => self.call(function, args, span),
+
+            Reporter::Import(path) => self.import(path),
+            Reporter::If { blocks, else_block } => {
+                let mut ty = None::<Type>;
+                for (condition, body) in blocks {
+                    self.expect(&Type::Bool, &condition.data, condition.span);
+                    let body = self.reporter(&body.data, body.span);
+                    ty = Some(ty.map_or(body.clone(), |ty| ty.join(&body)));
+                }
+                let other = match else_block {
+                    Some(body) => self.reporter(&body.data, body.span),
+                    None => Type::Null,
+                };
+                ty.map_or(other.clone(), |ty| ty.join(&other))
+            }
+
+            Reporter::Add { a, b }
+            | Reporter::Subtract { a, b }
+            | Reporter::Multiply { a, b }
+            | Reporter::Divide { a, b } => self.arithmetic(a, b),
+            Reporter::Exponent { a, b } => {
+                let a_ty = self.number(&a.data, a.span);
+                let b_ty = self.number(&b.data, b.span);
+                match (a_ty, b_ty) {
+                    (Type::Int, Type::Float) => {
+                        self.error("You cannot do arithmetic with floats and integers.", b.span);
+                        Type::Any
+                    }
+                    (Type::Int, _) => Type::Int,
+                    (Type::Float, _) => Type::Float,
+                    _ => Type::Any,
+                }
+            }
+            Reporter::Concat { a, b } => {
+                self.expect(&Type::String, &a.data, a.span);
+                self.expect(&Type::String, &b.data, b.span);
+                Type::String
+            }
+            Reporter::And { a, b } | Reporter::Or { a, b } => {
+                self.expect(&Type::Bool, &a.data, a.span);
+                self.expect(&Type::Bool, &b.data, b.span);
+                Type::Bool
+            }
+            Reporter::Coalesce { a, b } => {
+                let a_ty = self.reporter(&a.data, a.span);
+                let b_ty = self.reporter(&b.data, b.span);
+                match a_ty {
+                    Type::Null => b_ty,
+                    Type::Any => Type::Any,
+                    a_ty => a_ty.join(&b_ty),
+                }
+            }
+            Reporter::Equality { a, b } => {
+                self.reporter(&a.data, a.span);
+                self.reporter(&b.data, b.span);
+                Type::Bool
+            }
+            Reporter::Inequality { a, b, .. } => {
+                self.number(&a.data, a.span);
+                self.number(&b.data, b.span);
+                Type::Bool
+            }
+            Reporter::Not(value) => {
+                self.expect(&Type::Bool, &value.data, value.span);
+                Type::Bool
+            }
+            Reporter::Negative(value) => self.number(&value.data, value.span),
+        }
+    }
+}
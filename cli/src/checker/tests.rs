@@ -0,0 +1,93 @@
+use super::Checker;
+
+/// The debug output of each error the checker finds in `code`.
+fn check(code: &str) -> Vec<String> {
+    let (_, errors) = Checker::new().check_source(code);
+    errors.iter().map(|e| format!("{e:?}")).collect()
+}
+
+#[test]
+fn matching_annotations_are_accepted() {
+    let errors = check(
+        "
+        type Rectangle {width: int, height: int}
+        area = fn(r: Rectangle) -> int -> r.width * r.height;
+        count: int = area(Rectangle(2, 3));
+        name: string = \"box\";
+        ratio: float = 1.5;
+        ",
+    );
+    assert!(errors.is_empty(), "{errors:?}");
+}
+
+#[test]
+fn a_mismatched_variable_annotation_is_rejected() {
+    let errors = check("count: int = \"three\";");
+    assert_eq!(errors.len(), 1, "{errors:?}");
+}
+
+#[test]
+fn annotated_variables_keep_their_type_when_reassigned() {
+    assert!(check("count: int = 1; count = 2;").is_empty());
+    assert_eq!(check("count: int = 1; count = \"two\";").len(), 1);
+}
+
+#[test]
+fn a_mismatched_argument_is_rejected() {
+    let errors = check("double = fn(n: int) -> n * 2; double(\"two\");");
+    assert_eq!(errors.len(), 1, "{errors:?}");
+}
+
+#[test]
+fn the_return_type_is_checked_against_the_body() {
+    assert!(check("name = fn() -> string -> \"box\";").is_empty());
+    let errors = check("name = fn() -> string -> 3;");
+    assert!(
+        errors[0].contains("This function should return string, but it returns int."),
+        "{errors:?}"
+    );
+}
+
+#[test]
+fn calls_have_the_declared_return_type() {
+    let errors = check("count = fn() -> int -> 1; name: string = count();");
+    assert_eq!(errors.len(), 1, "{errors:?}");
+}
+
+#[test]
+fn unannotated_values_are_dynamic() {
+    let errors = check(
+        "
+        identity = fn(value) -> value;
+        identity(1);
+        identity(\"one\");
+        count: int = identity(null);
+        ",
+    );
+    assert!(errors.is_empty(), "{errors:?}");
+}
+
+#[test]
+fn native_signatures_are_checked() {
+    let errors = check("int.to_str(\"one\");");
+    assert_eq!(errors.len(), 1, "{errors:?}");
+}
+
+#[test]
+fn unknown_annotations_are_rejected() {
+    let errors = check("count: integer = 1;");
+    assert!(errors[0].contains("Unknown type 'integer'."), "{errors:?}");
+}
+
+#[test]
+fn record_fields_are_checked() {
+    let errors = check(
+        "
+        type Point {x: int, y: int}
+        p = Point(1, 2);
+        p.x = \"one\";
+        p.z = 3;
+        ",
+    );
+    assert_eq!(errors.len(), 2, "{errors:?}");
+}
@@ -0,0 +1,201 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+use lib::{
+    compat::function,
+    value::{Call, MapRef, Value},
+};
+
+/// A type known before the program runs. [`Type::Any`] is the dynamic type, used
+/// whenever the checker can't tell what a value will be.
+#[derive(Debug, Clone)]
+pub enum Type {
+    Any,
+
+    Null,
+    Int,
+    Float,
+    String,
+    Bool,
+    Array(Box<Type>),
+    /// A map whose keys aren't known.
+    Map,
+    /// A map with known fields. Records also carry the name of their type.
+    Object {
+        name: Option<String>,
+        fields: Rc<BTreeMap<String, Type>>,
+    },
+    Function(Option<Rc<Signature>>),
+    /// A map that already exists, like a standard library module.
+    Module(Rc<RefCell<dyn MapRef>>),
+}
+
+#[derive(Debug)]
+pub struct Signature {
+    pub parameters: Vec<Parameter>,
+    pub returns: Type,
+}
+
+#[derive(Debug)]
+pub struct Parameter {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub kind: ParameterKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+    Required,
+    Optional,
+    /// The type of a rest parameter is the type of each of its items.
+    Rest,
+}
+
+impl Type {
+    /// Parses a type name, as used by annotations and [`TypeName`](lib::compat::type_name::TypeName).
+    /// Returns `None` for names that aren't built in, like record types.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "*" => Self::Any,
+            "null" => Self::Null,
+            "int" => Self::Int,
+            "float" => Self::Float,
+            "string" => Self::String,
+            "bool" => Self::Bool,
+            "map" => Self::Map,
+            "function" => Self::Function(None),
+            "array" => Self::Array(Box::new(Self::Any)),
+            // Tuples from native functions.
+            name if name.starts_with('[') => Self::Array(Box::new(Self::Any)),
+            name if name.starts_with("map<") => Self::Map,
+            name => {
+                let item = name.strip_prefix("array<")?.strip_suffix('>')?;
+                Self::Array(Box::new(Self::from_name(item)?))
+            }
+        })
+    }
+
+    pub fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::String(_) => Self::String,
+            Value::Int(_) => Self::Int,
+            Value::Float(_) => Self::Float,
+            Value::Boolean(_) => Self::Bool,
+            Value::Function(function) => Self::Function(Signature::native(function.as_ref())),
+            Value::Array(items) => Self::Array(Box::new(
+                items
+                    .iter()
+                    .map(Self::from_value)
+                    .reduce(|a, b| a.join(&b))
+                    .unwrap_or(Self::Any),
+            )),
+            Value::Map(map) => Self::Object {
+                name: None,
+                fields: Rc::new(
+                    map.iter()
+                        .map(|(key, value)| (key.clone(), Self::from_value(value)))
+                        .collect(),
+                ),
+            },
+            Value::MapRef(map) => Self::Module(map.clone()),
+            // Methods are only known once the value exists.
+            Value::UserData(_) => Self::Any,
+        }
+    }
+
+    /// Whether a value of type `other` can be used where `self` is expected.
+    pub fn accepts(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Any, _) | (_, Self::Any) => true,
+            (Self::Null, Self::Null)
+            | (Self::Int, Self::Int)
+            | (Self::Float, Self::Float)
+            | (Self::String, Self::String)
+            | (Self::Bool, Self::Bool)
+            | (Self::Function(_), Self::Function(_)) => true,
+            (Self::Array(a), Self::Array(b)) => a.accepts(b),
+            (Self::Map, Self::Map | Self::Object { .. } | Self::Module(_)) => true,
+            (Self::Object { name: Some(a), .. }, Self::Object { name: Some(b), .. }) => a == b,
+            (Self::Object { name: None, .. }, Self::Object { .. } | Self::Map) => true,
+            _ => false,
+        }
+    }
+
+    /// The type of a value that could be either `self` or `other`.
+    pub fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Null, Self::Null)
+            | (Self::Int, Self::Int)
+            | (Self::Float, Self::Float)
+            | (Self::String, Self::String)
+            | (Self::Bool, Self::Bool) => self.clone(),
+            (Self::Array(a), Self::Array(b)) => Self::Array(Box::new(a.join(b))),
+            (Self::Object { name: Some(a), .. }, Self::Object { name: Some(b), .. }) if a == b => {
+                self.clone()
+            }
+            (Self::Function(_), Self::Function(_)) => Self::Function(None),
+            (
+                Self::Map | Self::Object { .. } | Self::Module(_),
+                Self::Map | Self::Object { .. } | Self::Module(_),
+            ) => Self::Map,
+            _ => Self::Any,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "*"),
+            Self::Null => write!(f, "null"),
+            Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::String => write!(f, "string"),
+            Self::Bool => write!(f, "bool"),
+            Self::Array(item) => write!(f, "array<{item}>"),
+            Self::Object {
+                name: Some(name), ..
+            } => write!(f, "{name}"),
+            Self::Map | Self::Object { .. } | Self::Module(_) => write!(f, "map"),
+            Self::Function(_) => write!(f, "function"),
+        }
+    }
+}
+
+impl Signature {
+    /// The signature of a native function, from the [`TypeName`](lib::compat::type_name::TypeName)s
+    /// of its parameters. Type names the checker doesn't know are treated as dynamic.
+    pub fn native(function: &dyn Call) -> Option<Rc<Self>> {
+        let signature = function.signature()?;
+        let required = signature.required();
+        let function::Signature {
+            parameters,
+            returns,
+        } = signature;
+        let names = function.parameter_names();
+        Some(Rc::new(Self {
+            parameters: parameters
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| Parameter {
+                    name: names
+                        .as_ref()
+                        .and_then(|names| names.get(i))
+                        .map(|name| name.to_string()),
+                    ty: Type::from_name(ty).unwrap_or(Type::Any),
+                    kind: if i < required {
+                        ParameterKind::Required
+                    } else {
+                        ParameterKind::Optional
+                    },
+                })
+                .collect(),
+            returns: Type::from_name(&returns).unwrap_or(Type::Any),
+        }))
+    }
+}
@@ -1,10 +1,12 @@
+use std::borrow::Cow;
+
 use lib::{
     error::Result,
-    instruction::{Comparison, Reporter},
+    instruction::{self, Comparison, Parameter, Reporter},
     span::{Chunk, Span},
 };
 
-use crate::parser::expression::{Expression, Operation, UnaryOperation};
+use crate::parser::expression::{Argument, Expression, ObjectEntry, Operation, UnaryOperation};
 
 use super::{Compile, CompileChunk, CompilerScope};
 
@@ -13,7 +15,7 @@ impl<'a> Compile<'a> for Expression<'a> {
     fn compile(self, span: Span, scope: &mut CompilerScope<'a, '_>) -> Result<Chunk<Self::Output>> {
         match self {
             Expression::Null => Ok(Chunk::new(Reporter::Null, span)),
-            Expression::String(str) => Ok(Chunk::new(Reporter::ConstStr(str), span)),
+            Expression::String(str) => Ok(Chunk::new(Reporter::ConstStr(Cow::Borrowed(str)), span)),
             Expression::Int(int) => Ok(Chunk::new(Reporter::ConstInt(int), span)),
             Expression::Float(float) => Ok(Chunk::new(Reporter::ConstFloat(float), span)),
             Expression::Boolean(bool) => Ok(Chunk::new(Reporter::ConstBool(bool), span)),
@@ -27,24 +29,57 @@ impl<'a> Compile<'a> for Expression<'a> {
                 )),
                 span,
             )),
-            Expression::Function { parameters, body } => {
+            Expression::Object(entries) => Ok(Chunk::new(
+                Reporter::Object(
+                    entries
+                        .into_iter()
+                        .map(|c| c.compile(scope))
+                        .collect::<Result<_>>()?,
+                ),
+                span,
+            )),
+            Expression::Function {
+                parameters,
+                returns,
+                body,
+            } => {
                 let mut scope = CompilerScope::new(Some(scope));
-                scope.variables.extend(parameters.iter().map(|p| p.data));
+                let parameters = parameters
+                    .into_iter()
+                    .map(|p| {
+                        let default = p.data.default.map(|d| d.compile(&mut scope)).transpose()?;
+                        scope.variables.push(p.data.name.data);
+                        Ok(Parameter {
+                            name: p.data.name,
+                            ty: p.data.ty,
+                            default,
+                            rest: p.data.rest,
+                        })
+                    })
+                    .collect::<Result<_>>()?;
 
                 Ok(Chunk::new(
                     Reporter::Function {
                         parameters,
+                        returns,
                         body: body.unbox().compile(&mut scope)?.as_box(),
                     },
                     span,
                 ))
             }
-            // TODO: check for variable in scope
             Expression::Variable(name) => Ok(Chunk::new(
                 Reporter::Get {
-                    map: Chunk::new(Reporter::Parent(scope.get_var(name).unwrap_or(0)), span)
-                        .as_box(),
+                    map: Chunk::new(
+                        Reporter::Parent(
+                            scope
+                                .get_var(name)
+                                .ok_or_else(|| scope.unresolved(name, span))?,
+                        ),
+                        span,
+                    )
+                    .as_box(),
                     name: Chunk::new(name, span),
+                    optional: false,
                 },
                 span,
             )),
@@ -52,6 +87,15 @@ impl<'a> Compile<'a> for Expression<'a> {
                 Reporter::Get {
                     map: map.unbox().compile(scope)?.as_box(),
                     name,
+                    optional: false,
+                },
+                span,
+            )),
+            Expression::OptionalProp(map, name) => Ok(Chunk::new(
+                Reporter::Get {
+                    map: map.unbox().compile(scope)?.as_box(),
+                    name,
+                    optional: true,
                 },
                 span,
             )),
@@ -59,6 +103,15 @@ impl<'a> Compile<'a> for Expression<'a> {
                 Reporter::DynGet {
                     map: map.unbox().compile(scope)?.as_box(),
                     attr: attr.unbox().compile(scope)?.as_box(),
+                    optional: false,
+                },
+                span,
+            )),
+            Expression::OptionalDynProp(map, attr) => Ok(Chunk::new(
+                Reporter::DynGet {
+                    map: map.unbox().compile(scope)?.as_box(),
+                    attr: attr.unbox().compile(scope)?.as_box(),
+                    optional: true,
                 },
                 span,
             )),
@@ -88,6 +141,36 @@ impl<'a> Compile<'a> for Expression<'a> {
                 },
                 span,
             )),
+            Expression::BinaryOp {
+                a,
+                b,
+                op:
+                    Chunk {
+                        data: Operation::Pipe,
+                        ..
+                    },
+            } => {
+                let a = a.unbox().compile(scope)?;
+                let piped = Chunk::new(instruction::Argument::Positional(a.data), a.span);
+                Ok(match b.unbox() {
+                    Chunk {
+                        data: Expression::Call { value, args },
+                        ..
+                    } => Chunk::new(
+                        Reporter::Call(
+                            value.unbox().compile(scope)?.as_box(),
+                            std::iter::once(Ok(piped))
+                                .chain(args.into_iter().map(|c| c.compile(scope)))
+                                .collect::<Result<_>>()?,
+                        ),
+                        span,
+                    ),
+                    func => Chunk::new(
+                        Reporter::Call(func.compile(scope)?.as_box(), vec![piped]),
+                        span,
+                    ),
+                })
+            }
             Expression::BinaryOp { a, b, op } => {
                 let a = a.unbox().compile(scope)?.as_box();
                 let b = b.unbox().compile(scope)?.as_box();
@@ -108,35 +191,25 @@ impl<'a> Compile<'a> for Expression<'a> {
                             b,
                             op: Chunk::new(Comparison::LessThan, op.span),
                         },
-                        Operation::LtEqual => Reporter::Not(
-                            Chunk::new(
-                                Reporter::Inequality {
-                                    a,
-                                    b,
-                                    op: Chunk::new(Comparison::GreaterThan, op.span),
-                                },
-                                span,
-                            )
-                            .as_box(),
-                        ),
+                        Operation::LtEqual => Reporter::Inequality {
+                            a,
+                            b,
+                            op: Chunk::new(Comparison::LessOrEqual, op.span),
+                        },
                         Operation::Gt => Reporter::Inequality {
                             a,
                             b,
                             op: Chunk::new(Comparison::GreaterThan, op.span),
                         },
-                        Operation::GtEqual => Reporter::Not(
-                            Chunk::new(
-                                Reporter::Inequality {
-                                    a,
-                                    b,
-                                    op: Chunk::new(Comparison::LessThan, op.span),
-                                },
-                                span,
-                            )
-                            .as_box(),
-                        ),
+                        Operation::GtEqual => Reporter::Inequality {
+                            a,
+                            b,
+                            op: Chunk::new(Comparison::GreaterOrEqual, op.span),
+                        },
                         Operation::And => Reporter::And { a, b },
                         Operation::Or => Reporter::Or { a, b },
+                        Operation::Coalesce => Reporter::Coalesce { a, b },
+                        Operation::Pipe => unreachable!(),
                     },
                     span,
                 ))
@@ -153,3 +226,43 @@ impl<'a> Compile<'a> for Expression<'a> {
         }
     }
 }
+
+impl<'a> Compile<'a> for ObjectEntry<'a> {
+    type Output = instruction::ObjectEntry<'a>;
+    fn compile(self, span: Span, scope: &mut CompilerScope<'a, '_>) -> Result<Chunk<Self::Output>> {
+        Ok(Chunk::new(
+            match self {
+                ObjectEntry::Field(key, value) => {
+                    instruction::ObjectEntry::Field(key, value.compile(scope)?)
+                }
+                ObjectEntry::Computed(key, value) => {
+                    instruction::ObjectEntry::Computed(key.compile(scope)?, value.compile(scope)?)
+                }
+                ObjectEntry::Spread(value) => {
+                    instruction::ObjectEntry::Spread(value.compile(span, scope)?.data)
+                }
+            },
+            span,
+        ))
+    }
+}
+
+impl<'a> Compile<'a> for Argument<'a> {
+    type Output = instruction::Argument<'a>;
+    fn compile(self, span: Span, scope: &mut CompilerScope<'a, '_>) -> Result<Chunk<Self::Output>> {
+        Ok(Chunk::new(
+            match self {
+                Argument::Positional(value) => {
+                    instruction::Argument::Positional(value.compile(span, scope)?.data)
+                }
+                Argument::Spread(value) => {
+                    instruction::Argument::Spread(value.compile(span, scope)?.data)
+                }
+                Argument::Named(name, value) => {
+                    instruction::Argument::Named(name, value.compile(scope)?)
+                }
+            },
+            span,
+        ))
+    }
+}
@@ -1,5 +1,5 @@
 use lib::{
-    error::Result,
+    error::{Error, Result},
     instruction::Instruction,
     span::{Chunk, Span},
 };
@@ -7,7 +7,10 @@ use lib::{
 use crate::{parser::block::Block, std_lib::prelude::Prelude};
 
 mod expression;
+pub mod optimize;
 mod statement;
+#[cfg(test)]
+mod tests;
 
 pub trait Compile<'a> {
     type Output;
@@ -45,6 +48,34 @@ impl<'a, 'b> CompilerScope<'a, 'b> {
         }
     }
 
+    /// The visible variable with the name closest to `name`, if any is close enough to
+    /// be a likely typo.
+    fn suggest(&self, name: &str) -> Option<&'a str> {
+        let max = (name.chars().count() / 3).max(1);
+        let mut best = None;
+        let mut scope = Some(self);
+        while let Some(s) = scope {
+            for var in &s.variables {
+                let distance = edit_distance(name, var);
+                if distance <= max && best.is_none_or(|(_, d)| distance < d) {
+                    best = Some((*var, distance));
+                }
+            }
+            scope = s.parent;
+        }
+        best.map(|(var, _)| var)
+    }
+
+    fn unresolved(&self, name: &str, span: Span) -> Error {
+        let message = match self.suggest(name) {
+            Some(suggestion) => {
+                format!("Cannot find variable '{name}'. Did you mean '{suggestion}'?")
+            }
+            None => format!("Cannot find variable '{name}'."),
+        };
+        Error::new(message, span)
+    }
+
     pub fn from_prelude() -> Self {
         Self {
             variables: Prelude::keys(),
@@ -53,19 +84,47 @@ impl<'a, 'b> CompilerScope<'a, 'b> {
     }
 }
 
-impl<'a> Compile<'a> for Block<'a> {
-    type Output = Vec<Chunk<Instruction<'a>>>;
-    fn compile(
+/// The number of single character insertions, deletions and substitutions needed to turn
+/// `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl<'a> Block<'a> {
+    /// Compiles the block without creating a new scope, for bodies that start out with
+    /// variables, like `for` loops.
+    pub fn compile_in(
         self,
         span: Span,
         scope: &mut CompilerScope<'a, '_>,
     ) -> Result<Chunk<Vec<Chunk<Instruction<'a>>>>> {
-        let mut scope = CompilerScope::new(Some(scope));
-
         let mut instructions = Vec::new();
         for statement in self.body {
-            instructions.push(statement.compile(&mut scope)?);
+            instructions.push(statement.compile(scope)?);
         }
         Ok(Chunk::new(instructions, span))
     }
 }
+
+impl<'a> Compile<'a> for Block<'a> {
+    type Output = Vec<Chunk<Instruction<'a>>>;
+    fn compile(
+        self,
+        span: Span,
+        scope: &mut CompilerScope<'a, '_>,
+    ) -> Result<Chunk<Vec<Chunk<Instruction<'a>>>>> {
+        let mut scope = CompilerScope::new(Some(scope));
+        self.compile_in(span, &mut scope)
+    }
+}
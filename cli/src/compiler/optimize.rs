@@ -0,0 +1,351 @@
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
+
+use lib::{
+    error::Result,
+    instruction::{Argument, Instruction, ObjectEntry, Parameter, Reporter},
+    span::Chunk,
+    value::Value,
+};
+
+use crate::{
+    engine::State,
+    interpreter::{Scope, evaluate},
+};
+
+fn is_constant(reporter: &Reporter) -> bool {
+    matches!(
+        reporter,
+        Reporter::Null
+            | Reporter::ConstStr(_)
+            | Reporter::ConstInt(_)
+            | Reporter::ConstFloat(_)
+            | Reporter::ConstBool(_)
+    )
+}
+
+/// Whether the reporter always evaluates to a boolean, so that `!!x` is the same as `x`.
+fn is_boolean(reporter: &Reporter) -> bool {
+    matches!(
+        reporter,
+        Reporter::ConstBool(_)
+            | Reporter::Not(_)
+            | Reporter::And { .. }
+            | Reporter::Or { .. }
+            | Reporter::Equality { .. }
+            | Reporter::Inequality { .. }
+    )
+}
+
+/// Folds constant expressions and removes branches that can never run. Constant
+/// operations are evaluated by the interpreter. Those that fail, like dividing by zero or
+/// overflowing an int, are left as they are, so the error is reported when they run.
+pub struct Optimizer {
+    /// An empty scope to evaluate constant operations in.
+    scope: Rc<RefCell<Scope>>,
+}
+
+impl Optimizer {
+    pub fn new(engine: Rc<State>) -> Self {
+        Self {
+            scope: Rc::new(RefCell::new(Scope::new(engine, None))),
+        }
+    }
+
+    pub fn block<'a>(
+        &self,
+        body: Vec<Chunk<Instruction<'a>>>,
+    ) -> Result<Vec<Chunk<Instruction<'a>>>> {
+        let mut optimized = Vec::with_capacity(body.len());
+        for instruction in body {
+            if let Some(instruction) = self.instruction(instruction)? {
+                optimized.push(instruction);
+            }
+        }
+        Ok(optimized)
+    }
+
+    fn instruction<'a>(
+        &self,
+        instruction: Chunk<Instruction<'a>>,
+    ) -> Result<Option<Chunk<Instruction<'a>>>> {
+        let span = instruction.span;
+        Ok(Some(Chunk::new(
+            match instruction.data {
+                Instruction::Set {
+                    map,
+                    name,
+                    value,
+                    ty,
+                } => Instruction::Set {
+                    map: self.reporter(map)?,
+                    name,
+                    value: self.reporter(value)?,
+                    ty,
+                },
+                Instruction::While { condition, body } => {
+                    let condition = self.reporter(condition)?;
+                    if let Reporter::ConstBool(false) = condition.data {
+                        return Ok(None);
+                    }
+                    Instruction::While {
+                        condition,
+                        body: Chunk::new(self.block(body.data)?, body.span),
+                    }
+                }
+                Instruction::For { name, iter, body } => Instruction::For {
+                    name,
+                    iter: self.reporter(iter)?,
+                    body: Chunk::new(self.block(body.data)?, body.span),
+                },
+                Instruction::Return(value) => Instruction::Return(self.reporter(value)?),
+                Instruction::Void(reporter) => {
+                    Instruction::Void(self.reporter(Chunk::new(reporter, span))?.data)
+                }
+            },
+            span,
+        )))
+    }
+
+    /// Evaluates an operation whose operands are all constants.
+    fn fold<'a>(&self, reporter: Chunk<Reporter<'a>>) -> Chunk<Reporter<'a>> {
+        let span = reporter.span;
+        let Ok(value) = evaluate(&self.scope, reporter.clone()) else {
+            return reporter;
+        };
+        Chunk::new(
+            match value {
+                Value::Null => Reporter::Null,
+                Value::String(str) => Reporter::ConstStr(Cow::Owned(str)),
+                Value::Int(int) => Reporter::ConstInt(int),
+                Value::Float(float) => Reporter::ConstFloat(float),
+                Value::Boolean(bool) => Reporter::ConstBool(bool),
+                _ => unreachable!("operations on constants evaluate to constants"),
+            },
+            span,
+        )
+    }
+
+    fn boxed<'a>(&self, reporter: Chunk<Box<Reporter<'a>>>) -> Result<Chunk<Box<Reporter<'a>>>> {
+        Ok(self.reporter(reporter.unbox())?.as_box())
+    }
+
+    pub fn reporter<'a>(&self, reporter: Chunk<Reporter<'a>>) -> Result<Chunk<Reporter<'a>>> {
+        let span = reporter.span;
+        let reporter = match reporter.data {
+            Reporter::Block(body) => Reporter::Block(self.block(body)?),
+            Reporter::Array(items) => Reporter::Array(Chunk::new(
+                items
+                    .data
+                    .into_iter()
+                    .map(|item| self.reporter(item))
+                    .collect::<Result<_>>()?,
+                items.span,
+            )),
+            Reporter::Object(entries) => Reporter::Object(
+                entries
+                    .into_iter()
+                    .map(|entry| {
+                        let entry_span = entry.span;
+                        Ok(Chunk::new(
+                            match entry.data {
+                                ObjectEntry::Field(key, value) => {
+                                    ObjectEntry::Field(key, self.reporter(value)?)
+                                }
+                                ObjectEntry::Computed(key, value) => ObjectEntry::Computed(
+                                    self.reporter(key)?,
+                                    self.reporter(value)?,
+                                ),
+                                ObjectEntry::Spread(value) => ObjectEntry::Spread(
+                                    self.reporter(Chunk::new(value, entry_span))?.data,
+                                ),
+                            },
+                            entry_span,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            Reporter::Function {
+                parameters,
+                returns,
+                body,
+            } => Reporter::Function {
+                parameters: parameters
+                    .into_iter()
+                    .map(|parameter| {
+                        Ok(Parameter {
+                            default: parameter
+                                .default
+                                .map(|item| self.reporter(item))
+                                .transpose()?,
+                            ..parameter
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+                returns,
+                body: self.boxed(body)?,
+            },
+            Reporter::Get {
+                map,
+                name,
+                optional,
+            } => Reporter::Get {
+                map: self.boxed(map)?,
+                name,
+                optional,
+            },
+            Reporter::DynGet {
+                map,
+                attr,
+                optional,
+            } => Reporter::DynGet {
+                map: self.boxed(map)?,
+                attr: self.boxed(attr)?,
+                optional,
+            },
+            Reporter::Call(function, args) => Reporter::Call(
+                self.boxed(function)?,
+                args.into_iter()
+                    .map(|arg| {
+                        let arg_span = arg.span;
+                        Ok(Chunk::new(
+                            match arg.data {
+                                Argument::Positional(value) => Argument::Positional(
+                                    self.reporter(Chunk::new(value, arg_span))?.data,
+                                ),
+                                Argument::Spread(value) => Argument::Spread(
+                                    self.reporter(Chunk::new(value, arg_span))?.data,
+                                ),
+                                Argument::Named(name, value) => {
+                                    Argument::Named(name, self.reporter(value)?)
+                                }
+                            },
+                            arg_span,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            Reporter::If { blocks, else_block } => {
+                let mut arms = Vec::new();
+                let mut else_block = else_block.map(|body| self.boxed(body)).transpose()?;
+                for (condition, body) in blocks {
+                    let condition = self.reporter(condition)?;
+                    match condition.data {
+                        Reporter::ConstBool(false) => (),
+                        // Nothing after an arm that always runs can be reached.
+                        Reporter::ConstBool(true) => {
+                            else_block = Some(self.reporter(body)?.as_box());
+                            break;
+                        }
+                        _ => arms.push((condition, self.reporter(body)?)),
+                    }
+                }
+                if arms.is_empty() {
+                    return Ok(else_block.map_or(Chunk::new(Reporter::Null, span), Chunk::unbox));
+                }
+                Reporter::If {
+                    blocks: arms,
+                    else_block,
+                }
+            }
+
+            Reporter::Add { a, b } => {
+                self.fold_binary(|a, b| Reporter::Add { a, b }, a, b, span)?
+            }
+            Reporter::Subtract { a, b } => {
+                self.fold_binary(|a, b| Reporter::Subtract { a, b }, a, b, span)?
+            }
+            Reporter::Multiply { a, b } => {
+                self.fold_binary(|a, b| Reporter::Multiply { a, b }, a, b, span)?
+            }
+            Reporter::Divide { a, b } => {
+                self.fold_binary(|a, b| Reporter::Divide { a, b }, a, b, span)?
+            }
+            Reporter::Exponent { a, b } => {
+                self.fold_binary(|a, b| Reporter::Exponent { a, b }, a, b, span)?
+            }
+            Reporter::Concat { a, b } => {
+                self.fold_binary(|a, b| Reporter::Concat { a, b }, a, b, span)?
+            }
+            Reporter::Equality { a, b } => {
+                self.fold_binary(|a, b| Reporter::Equality { a, b }, a, b, span)?
+            }
+            Reporter::Inequality { a, b, op } => {
+                self.fold_binary(|a, b| Reporter::Inequality { a, b, op }, a, b, span)?
+            }
+            Reporter::And { a, b } => {
+                let a = self.boxed(a)?;
+                if let Reporter::ConstBool(false) = *a.data {
+                    return Ok(a.unbox());
+                }
+                self.fold_binary(|a, b| Reporter::And { a, b }, a, b, span)?
+            }
+            Reporter::Or { a, b } => {
+                let a = self.boxed(a)?;
+                if let Reporter::ConstBool(true) = *a.data {
+                    return Ok(a.unbox());
+                }
+                self.fold_binary(|a, b| Reporter::Or { a, b }, a, b, span)?
+            }
+            Reporter::Coalesce { a, b } => {
+                let a = self.boxed(a)?;
+                match *a.data {
+                    Reporter::Null => return self.reporter(b.unbox()),
+                    ref constant if is_constant(constant) => return Ok(a.unbox()),
+                    _ => Reporter::Coalesce {
+                        a,
+                        b: self.boxed(b)?,
+                    },
+                }
+            }
+            Reporter::Not(value) => {
+                let value = self.boxed(value)?;
+                match *value.data {
+                    Reporter::Not(inner) if is_boolean(&inner.data) => return Ok(inner.unbox()),
+                    ref constant if is_constant(constant) => {
+                        return Ok(self.fold(Chunk::new(Reporter::Not(value), span)));
+                    }
+                    data => Reporter::Not(Chunk::new(Box::new(data), value.span)),
+                }
+            }
+            Reporter::Negative(value) => {
+                let value = self.boxed(value)?;
+                if is_constant(&value.data) {
+                    return Ok(self.fold(Chunk::new(Reporter::Negative(value), span)));
+                }
+                Reporter::Negative(value)
+            }
+
+            reporter @ (Reporter::Parent(_)
+            | Reporter::Null
+            | Reporter::ConstStr(_)
+            | Reporter::ConstInt(_)
+            | Reporter::ConstFloat(_)
+            | Reporter::ConstBool(_)
+            | Reporter::RecordType { .. }
+            | Reporter::Import(_)) => reporter,
+        };
+        Ok(Chunk::new(reporter, span))
+    }
+
+    /// Optimizes both operands, then folds the operation if they are both constant.
+    fn fold_binary<'a>(
+        &self,
+        build: impl FnOnce(Chunk<Box<Reporter<'a>>>, Chunk<Box<Reporter<'a>>>) -> Reporter<'a>,
+        a: Chunk<Box<Reporter<'a>>>,
+        b: Chunk<Box<Reporter<'a>>>,
+        span: lib::span::Span,
+    ) -> Result<Reporter<'a>> {
+        let a = self.boxed(a)?;
+        let b = self.boxed(b)?;
+        let constant = is_constant(&a.data) && is_constant(&b.data);
+        let reporter = build(a, b);
+        if constant {
+            Ok(self.fold(Chunk::new(reporter, span)).data)
+        } else {
+            Ok(reporter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
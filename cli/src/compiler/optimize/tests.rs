@@ -0,0 +1,91 @@
+use lib::instruction::{Instruction, Reporter};
+
+use super::Optimizer;
+use crate::{compile, engine::Engine};
+
+/// Optimizes `code` and gives back what its last statement returns.
+fn returned(code: &str) -> Reporter<'_> {
+    let engine = Engine::new();
+    let program = Optimizer::new(engine.state().clone())
+        .block(compile(code).unwrap().data)
+        .unwrap();
+    match program
+        .into_iter()
+        .last()
+        .map(|instruction| instruction.data)
+    {
+        Some(Instruction::Return(value)) => value.data,
+        instruction => panic!("expected a return, found {instruction:?}"),
+    }
+}
+
+#[test]
+fn folds_constant_arithmetic() {
+    assert!(matches!(
+        returned("return 1 + 2 * 3;"),
+        Reporter::ConstInt(7)
+    ));
+    assert!(matches!(
+        returned("return 2 * 4 == 8;"),
+        Reporter::ConstBool(true)
+    ));
+    assert!(matches!(returned("return \"a\" .. \"b\";"), Reporter::ConstStr(s) if s == "ab"));
+}
+
+#[test]
+fn leaves_operations_that_fail_unfolded() {
+    assert!(matches!(
+        returned("return 2147483647 + 1;"),
+        Reporter::Add { .. }
+    ));
+    assert!(matches!(returned("return 1 / 0;"), Reporter::Divide { .. }));
+    assert!(matches!(
+        returned("return 1 + \"a\";"),
+        Reporter::Add { .. }
+    ));
+}
+
+#[test]
+fn removes_branches_that_never_run() {
+    assert!(matches!(
+        returned("x = 1; return if false -> x; else -> 2;;"),
+        Reporter::ConstInt(2)
+    ));
+    assert!(matches!(
+        returned("x = 1; return if true -> 2; else -> x;;"),
+        Reporter::ConstInt(2)
+    ));
+    let Reporter::If { blocks, else_block } =
+        returned("x = true; return if false -> 1; else if x -> 2; else -> 3;;")
+    else {
+        panic!("expected the if to be kept");
+    };
+    assert_eq!(blocks.len(), 1);
+    assert!(else_block.is_some());
+}
+
+#[test]
+fn removes_double_negation_of_booleans_only() {
+    assert!(matches!(
+        returned("x = 1; return !!(x == 1);"),
+        Reporter::Equality { .. }
+    ));
+    // `!!x` checks that `x` is a boolean, so it stays when that isn't known.
+    assert!(matches!(returned("x = 1; return !!x;"), Reporter::Not(_)));
+}
+
+#[test]
+fn short_circuits_constant_logic() {
+    assert!(matches!(
+        returned("x = 1; return false && x;"),
+        Reporter::ConstBool(false)
+    ));
+    assert!(matches!(
+        returned("x = 1; return true || x;"),
+        Reporter::ConstBool(true)
+    ));
+    assert!(!matches!(
+        returned("x = 1; return null ?? x;"),
+        Reporter::Coalesce { .. }
+    ));
+}
@@ -4,9 +4,12 @@ use lib::{
     span::{Chunk, Span},
 };
 
-use crate::parser::{
-    expression::Expression,
-    statement::{AssignOperator, Statement},
+use crate::{
+    parser::{
+        expression::Expression,
+        statement::{AssignOperator, Statement},
+    },
+    std_lib::types::BUILT_IN_TYPES,
 };
 
 use super::{Compile, CompileChunk, CompilerScope};
@@ -23,12 +26,15 @@ impl<'a> Compile<'a> for Statement<'a> {
                 name: name_expr,
                 op,
                 value,
+                ty,
             } => {
                 let (name, map) = match name_expr.data {
-                    // TODO: check parent scopes
                     Expression::Variable(name) => {
                         let up = scope.get_var(name);
                         if up.is_none() {
+                            if op.data != AssignOperator::Set {
+                                return Err(scope.unresolved(name, name_expr.span));
+                            }
                             scope.variables.push(name);
                         }
                         (
@@ -56,6 +62,7 @@ impl<'a> Compile<'a> for Statement<'a> {
                                 Reporter::Get {
                                     map: map.clone().as_box(),
                                     name,
+                                    optional: false,
                                 },
                                 name_expr.span,
                             )
@@ -70,6 +77,7 @@ impl<'a> Compile<'a> for Statement<'a> {
                                 Reporter::Get {
                                     map: map.clone().as_box(),
                                     name,
+                                    optional: false,
                                 },
                                 name_expr.span,
                             )
@@ -84,6 +92,7 @@ impl<'a> Compile<'a> for Statement<'a> {
                                 Reporter::Get {
                                     map: map.clone().as_box(),
                                     name,
+                                    optional: false,
                                 },
                                 name_expr.span,
                             )
@@ -98,6 +107,7 @@ impl<'a> Compile<'a> for Statement<'a> {
                                 Reporter::Get {
                                     map: map.clone().as_box(),
                                     name,
+                                    optional: false,
                                 },
                                 name_expr.span,
                             )
@@ -108,7 +118,15 @@ impl<'a> Compile<'a> for Statement<'a> {
                     ),
                 };
 
-                Ok(Chunk::new(Instruction::Set { map, name, value }, span))
+                Ok(Chunk::new(
+                    Instruction::Set {
+                        map,
+                        name,
+                        value,
+                        ty,
+                    },
+                    span,
+                ))
             }
             Self::Expr(expression) => Ok(Chunk::new(
                 Instruction::Void(expression.compile(span, scope)?.data),
@@ -121,14 +139,58 @@ impl<'a> Compile<'a> for Statement<'a> {
                 },
                 span,
             )),
-            Self::For { name, iter, body } => Ok(Chunk::new(
-                Instruction::For {
-                    name,
-                    iter: iter.compile(scope)?,
-                    body: body.compile(scope)?,
-                },
-                span,
-            )),
+            Self::For { name, iter, body } => {
+                let iter = iter.compile(scope)?;
+                let mut body_scope = CompilerScope::new(Some(scope));
+                body_scope.variables.push(name.data);
+                Ok(Chunk::new(
+                    Instruction::For {
+                        name,
+                        iter,
+                        body: body.data.compile_in(body.span, &mut body_scope)?,
+                    },
+                    span,
+                ))
+            }
+            Self::Extern(names) => {
+                for name in names {
+                    if scope.get_var(name.data).is_none() {
+                        scope.variables.push(name.data);
+                    }
+                }
+                Ok(Chunk::new(Instruction::Void(Reporter::Null), span))
+            }
+            Self::Type { name, fields } => {
+                let up = scope.get_var(name.data);
+                if up.is_none() {
+                    scope.variables.push(name.data);
+                }
+                // Fields can be of another record type in scope, including this one.
+                for field in &fields {
+                    let ty = field.data.ty;
+                    if !BUILT_IN_TYPES.contains(&ty.data) && scope.get_var(ty.data).is_none() {
+                        return Err(Error::new(format!("Unknown type '{}'.", ty.data), ty.span));
+                    }
+                }
+                Ok(Chunk::new(
+                    Instruction::Set {
+                        map: Chunk::new(Reporter::Parent(up.unwrap_or(0)), name.span),
+                        name,
+                        value: Chunk::new(
+                            Reporter::RecordType {
+                                name,
+                                fields: fields
+                                    .into_iter()
+                                    .map(|field| (field.data.name, field.data.ty))
+                                    .collect(),
+                            },
+                            span,
+                        ),
+                        ty: None,
+                    },
+                    span,
+                ))
+            }
             Self::Return(value) => Ok(Chunk::new(Instruction::Return(value.compile(scope)?), span)),
         }
     }
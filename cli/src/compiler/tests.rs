@@ -0,0 +1,53 @@
+use super::edit_distance;
+use crate::compile;
+
+/// The message and the text that the error for `code` points at.
+fn unresolved(code: &str) -> (String, &str) {
+    let error = compile(code).expect_err("expected the code not to compile");
+    let span = error.span();
+    let message = format!("{error:?}");
+    (message, &code[span.start.col..span.end.col])
+}
+
+#[test]
+fn a_misspelled_local_suggests_the_local() {
+    let (message, at) = unresolved("counter = 1; return countr + 1;");
+    assert!(
+        message.contains("Cannot find variable 'countr'. Did you mean 'counter'?"),
+        "{message}"
+    );
+    assert_eq!(at, "countr");
+}
+
+#[test]
+fn a_misspelled_local_in_a_closure_suggests_the_outer_variable() {
+    let (message, _) = unresolved("total = 0; add = fn(n) -> totl + n;");
+    assert!(message.contains("Did you mean 'total'?"), "{message}");
+}
+
+#[test]
+fn a_misspelled_prelude_name_suggests_the_prelude_name() {
+    let (message, at) = unresolved("printn(1);");
+    assert!(
+        message.contains("Cannot find variable 'printn'. Did you mean 'println'?"),
+        "{message}"
+    );
+    assert_eq!(at, "printn");
+}
+
+#[test]
+fn a_name_with_no_close_match_has_no_suggestion() {
+    let (message, _) = unresolved("return zzyzx;");
+    assert!(
+        message.contains("Cannot find variable 'zzyzx'.\""),
+        "{message}"
+    );
+}
+
+#[test]
+fn edit_distance_counts_single_character_edits() {
+    assert_eq!(edit_distance("counter", "counter"), 0);
+    assert_eq!(edit_distance("countr", "counter"), 1);
+    assert_eq!(edit_distance("cuonter", "counter"), 2);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
@@ -0,0 +1,481 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fs,
+    rc::{Rc, Weak},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use lib::{
+    compat::context::Host,
+    error::{Error, ErrorKind, Limit, Result},
+    gc::{self, Stats, Tracker},
+    span::Span,
+    value::Value,
+};
+
+use crate::{
+    compile,
+    compiler::optimize::Optimizer,
+    interpreter::{Scope, run_block},
+    std_lib::{self, prelude::Prelude},
+};
+
+/// How many nested calls are allowed unless the [`Limits`] say otherwise. Calls in tail
+/// position don't count towards the limit.
+pub const DEFAULT_MAX_DEPTH: usize = 200;
+
+/// How much native stack a run may use unless the [`Limits`] say otherwise. Calls are
+/// evaluated recursively, and this leaves room for the host on an 8 MB main thread.
+pub const DEFAULT_MAX_STACK: usize = 4 * 1024 * 1024;
+
+/// How often, in steps, the clock is checked against [`Limits::timeout`] and the
+/// [`Interrupt`] handle is checked.
+const STEPS_PER_CHECK: u64 = 256;
+
+/// Limits on what a single [`Engine::run`] may use, for running scripts that aren't
+/// trusted. Going over a limit fails with [`ErrorKind::LimitExceeded`].
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// How many expressions and instructions may be evaluated.
+    pub max_steps: Option<u64>,
+    /// How deeply functions may call each other.
+    pub max_depth: usize,
+    /// How many bytes of native stack may be used, counted from where [`Engine::run`] was
+    /// called. This must fit in the stack of the thread running the engine, or a deep
+    /// enough script overflows it and aborts the process.
+    pub max_stack: usize,
+    /// How many items an array or map may hold.
+    pub max_collection_size: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_steps: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_stack: DEFAULT_MAX_STACK,
+            max_collection_size: None,
+            timeout: None,
+        }
+    }
+}
+
+/// What scripts are allowed to reach outside of the engine. Everything is allowed by
+/// default.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    /// Importing modules by file path, rather than registered modules.
+    pub file_imports: bool,
+    /// Native modules that read or write files, like `std/fs`.
+    pub file_system: bool,
+    /// Native modules that run or inspect processes.
+    pub process: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            file_imports: true,
+            file_system: true,
+            process: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    FileImports,
+    FileSystem,
+    Process,
+}
+
+impl FromStr for Capability {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "imports" => Ok(Self::FileImports),
+            "fs" => Ok(Self::FileSystem),
+            "process" => Ok(Self::Process),
+            _ => Err(format!("There is no capability named '{name}'.")),
+        }
+    }
+}
+
+impl Capabilities {
+    /// Allows nothing outside of the engine.
+    pub fn none() -> Self {
+        Self {
+            file_imports: false,
+            file_system: false,
+            process: false,
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::FileImports => self.file_imports,
+            Capability::FileSystem => self.file_system,
+            Capability::Process => self.process,
+        }
+    }
+
+    pub fn allow(&mut self, capability: Capability) {
+        match capability {
+            Capability::FileImports => self.file_imports = true,
+            Capability::FileSystem => self.file_system = true,
+            Capability::Process => self.process = true,
+        }
+    }
+}
+
+/// Stops a running script from another thread, or from a signal handler. The script
+/// fails with [`ErrorKind::Interrupted`] at its next check, and keeps failing until the
+/// run it was in has returned, so native code that ignores errors can't keep it going.
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// An interpreter instance. Each engine has its own prelude, module cache and settings,
+/// so any number of them can be used side by side on one thread.
+pub struct Engine {
+    state: Rc<State>,
+}
+
+/// The part of an engine that running code refers back to. Every scope and function
+/// holds on to the state of the engine that created it.
+#[derive(Debug)]
+pub struct State {
+    prelude: Rc<RefCell<Prelude>>,
+    modules: RefCell<HashMap<String, Module>>,
+    limits: Cell<Limits>,
+    capabilities: Cell<Capabilities>,
+    /// How many calls deep the running code is.
+    pub depth: Cell<usize>,
+    /// The address of the stack where the outermost run started.
+    stack_base: Cell<Option<usize>>,
+    steps: Cell<u64>,
+    deadline: Cell<Option<Instant>>,
+    interrupt: Interrupt,
+    /// Warnings from native functions, kept until the host takes them.
+    warnings: RefCell<Vec<Error>>,
+    /// The maps this engine's cycle collector starts from.
+    pub tracker: Tracker,
+    /// Lets native functions import modules through [`Host`], which only has `&self`.
+    this: Weak<State>,
+}
+
+#[derive(Debug)]
+struct Module {
+    value: Value,
+    requires: Option<Capability>,
+}
+
+impl Engine {
+    /// Creates an engine with the standard library available to import.
+    pub fn new() -> Self {
+        let engine = Self {
+            state: Rc::new_cyclic(|this| State {
+                prelude: Rc::new(RefCell::new(Prelude::new())),
+                modules: RefCell::new(HashMap::new()),
+                limits: Cell::new(Limits::default()),
+                capabilities: Cell::new(Capabilities::default()),
+                depth: Cell::new(0),
+                stack_base: Cell::new(None),
+                steps: Cell::new(0),
+                deadline: Cell::new(None),
+                interrupt: Interrupt::default(),
+                warnings: RefCell::new(Vec::new()),
+                tracker: Tracker::default(),
+                this: this.clone(),
+            }),
+        };
+        std_lib::register(&engine);
+        engine
+    }
+
+    /// Makes a value importable with `import(path)`.
+    pub fn register_module(&self, path: &str, module: impl Into<Value>) {
+        self.state.insert_module(path, module.into(), None);
+    }
+
+    /// Makes a native module importable, but only while the engine has the capability
+    /// it needs.
+    pub fn register_privileged_module(
+        &self,
+        path: &str,
+        requires: Capability,
+        module: impl Into<Value>,
+    ) {
+        self.state
+            .insert_module(path, module.into(), Some(requires));
+    }
+
+    /// A module that has been registered or imported.
+    pub fn module(&self, path: &str) -> Option<Value> {
+        let modules = self.state.modules.borrow();
+        modules.get(path).map(|module| module.value.clone())
+    }
+
+    pub fn prelude(&self) -> &Rc<RefCell<Prelude>> {
+        &self.state.prelude
+    }
+
+    pub fn set_limits(&self, limits: Limits) {
+        self.state.limits.set(limits);
+    }
+
+    pub fn set_capabilities(&self, capabilities: Capabilities) {
+        self.state.capabilities.set(capabilities);
+    }
+
+    /// A handle that stops whatever this engine is running.
+    pub fn interrupt_handle(&self) -> Interrupt {
+        self.state.interrupt.clone()
+    }
+
+    /// Frees maps and functions that only reference cycles keep alive, like `std/gc`'s
+    /// `collect` does from a script. Long-running hosts can call this between runs.
+    pub fn collect_garbage(&self) -> Stats {
+        gc::collect(&self.state.tracker)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn state(&self) -> &Rc<State> {
+        &self.state
+    }
+
+    /// The warnings given since they were last taken.
+    pub fn take_warnings(&self) -> Vec<Error> {
+        self.state.warnings.take()
+    }
+
+    /// Runs a module, returning the value it returns or its top level scope. The step
+    /// count and timeout start again from zero for each run, and an interrupt only stops
+    /// the run it happened during. Interrupts sent while nothing is running are ignored.
+    pub fn run(&self, code: &str) -> Result<Value> {
+        let state = &self.state;
+        state.steps.set(0);
+        state.deadline.set(
+            state
+                .limits
+                .get()
+                .timeout
+                .map(|timeout| Instant::now() + timeout),
+        );
+        let outermost = state.stack_base.get().is_none();
+        if outermost {
+            state.interrupt.reset();
+            state.stack_base.set(Some(stack_address()));
+        }
+        let result = state.run(code);
+        if outermost {
+            state.stack_base.set(None);
+            state.interrupt.reset();
+        }
+        result
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        // Imported modules refer back to the state through their scopes.
+        self.state.modules.borrow_mut().clear();
+    }
+}
+
+/// An address in the current stack frame.
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+fn limit_exceeded(limit: Limit, message: String, span: Span) -> Error {
+    Error::new(message, span).with_kind(ErrorKind::LimitExceeded(limit))
+}
+
+impl State {
+    fn insert_module(&self, path: &str, value: Value, requires: Option<Capability>) {
+        self.modules
+            .borrow_mut()
+            .insert(path.to_string(), Module { value, requires });
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.limits.get().max_depth
+    }
+
+    pub fn depth_exceeded(&self, span: Span) -> Error {
+        let max = self.max_depth();
+        limit_exceeded(
+            Limit::Depth,
+            format!("Maximum recursion depth exceeded ({max} calls)."),
+            span,
+        )
+    }
+
+    /// Checks that the native stack used since the run started is within the limit, so
+    /// deep recursion fails with an error rather than overflowing the stack.
+    pub fn check_stack(&self, span: Span) -> Result<()> {
+        let Some(base) = self.stack_base.get() else {
+            return Ok(());
+        };
+        let max = self.limits.get().max_stack;
+        if base.abs_diff(stack_address()) > max {
+            return Err(limit_exceeded(
+                Limit::Stack,
+                format!("The script used more than {max} bytes of stack."),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Counts one step of evaluation against the step and time limits, and stops if the
+    /// engine has been interrupted.
+    pub fn step(&self, span: Span) -> Result<()> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        let limits = self.limits.get();
+        if let Some(max) = limits.max_steps
+            && steps > max
+        {
+            return Err(limit_exceeded(
+                Limit::Steps,
+                format!("The script ran for more than {max} steps."),
+                span,
+            ));
+        }
+        if !steps.is_multiple_of(STEPS_PER_CHECK) {
+            return Ok(());
+        }
+        if self.interrupt.is_set() {
+            return Err(
+                Error::new("The script was interrupted.", span).with_kind(ErrorKind::Interrupted)
+            );
+        }
+        if let Some(deadline) = self.deadline.get()
+            && Instant::now() > deadline
+        {
+            let timeout = limits.timeout.unwrap_or_default();
+            return Err(limit_exceeded(
+                Limit::Time,
+                format!("The script ran for longer than {timeout:?}."),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that a collection with `len` items is within the size limit.
+    pub fn check_size(&self, len: usize, span: Span) -> Result<()> {
+        match self.limits.get().max_collection_size {
+            Some(max) if len > max => Err(limit_exceeded(
+                Limit::CollectionSize,
+                format!("Collections can hold at most {max} items, but this one has {len}."),
+                span,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the size of a value, if it is a collection.
+    pub fn check_value(&self, value: &Value, span: Span) -> Result<()> {
+        match value {
+            Value::Array(items) => self.check_size(items.len(), span),
+            Value::Map(map) => self.check_size(map.len(), span),
+            Value::MapRef(map) => match map.try_borrow() {
+                Ok(map) => self.check_size(map.as_hashmap().map_or(0, |map| map.len()), span),
+                Err(_) => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn run(self: &Rc<Self>, code: &str) -> Result<Value> {
+        let scope = Scope::new_shared(self.clone(), Some(self.prelude.clone()));
+        let instruction_set = Optimizer::new(self.clone()).block(compile(code)?.data)?;
+        //dbg!(&instruction_set);
+
+        run_block(&scope, instruction_set)
+    }
+
+    /// Gets a registered module, or runs the module at `path` the first time it is imported.
+    pub fn import(self: &Rc<Self>, path: &str, span: Span) -> Result<Value> {
+        let capabilities = self.capabilities.get();
+        if let Some(module) = self.modules.borrow().get(path) {
+            if let Some(capability) = module.requires
+                && !capabilities.allows(capability)
+            {
+                return Err(Error::new(
+                    format!("This engine doesn't allow importing '{path}'."),
+                    span,
+                )
+                .with_kind(ErrorKind::NotPermitted));
+            }
+            return Ok(module.value.clone());
+        }
+        if !capabilities.allows(Capability::FileImports) {
+            return Err(Error::new(
+                format!("This engine doesn't allow importing files, like '{path}'."),
+                span,
+            )
+            .with_kind(ErrorKind::NotPermitted));
+        }
+
+        let code = fs::read_to_string(path)
+            .map_err(|_| Error::new(format!("The module path '{path}' does not exist."), span))?;
+        let module = self.run(&code)?;
+        self.insert_module(path, module.clone(), None);
+        Ok(module)
+    }
+}
+
+impl Host for State {
+    fn import(&self, path: &str, span: Span) -> Result<Value> {
+        // The state is only reachable while the engine holding it is alive.
+        State::import(&self.this.upgrade().unwrap(), path, span)
+    }
+
+    fn warn(&self, message: String, span: Span) {
+        self.warnings.borrow_mut().push(Error::new(message, span));
+    }
+
+    fn tracker(&self) -> &Tracker {
+        &self.tracker
+    }
+
+    fn step(&self, span: Span) -> Result<()> {
+        State::step(self, span)
+    }
+
+    fn check_size(&self, len: usize, span: Span) -> Result<()> {
+        State::check_size(self, len, span)
+    }
+}
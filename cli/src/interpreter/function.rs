@@ -1,36 +1,219 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{any::Any, cell::RefCell, rc::Rc};
 
 use lib::{
-    error::Result,
-    instruction::{Reporter, owned::OwnedReporter},
+    error::{Error, Result},
+    gc::Edge,
+    instruction::{Parameter, Reporter, owned::OwnedReporter},
     span::{Chunk, Span},
-    value::{Call, Value},
+    value::{Call, MapRef, Value},
 };
 
-use super::{Scope, evaluate};
+use crate::engine::State;
+
+use super::{Scope, Tail, call_owned, evaluate, evaluate_tail};
+
+/// A call that hasn't returned yet. Dropping it leaves the call.
+struct Frame<'e>(&'e State);
+
+impl<'e> Frame<'e> {
+    fn enter(engine: &'e State, span: Span) -> Result<Self> {
+        if engine.depth.get() >= engine.max_depth() {
+            return Err(engine.depth_exceeded(span));
+        }
+        engine.check_stack(span)?;
+        engine.depth.set(engine.depth.get() + 1);
+        Ok(Self(engine))
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        self.0.depth.set(self.0.depth.get() - 1);
+    }
+}
+
+struct FunctionParameter {
+    name: String,
+    default: Option<OwnedReporter>,
+    rest: bool,
+}
 
 pub struct Function {
-    parent: Rc<RefCell<Scope>>,
-    parameters: Vec<String>,
+    engine: Rc<State>,
+    parent: Rc<RefCell<dyn MapRef>>,
+    parameters: Vec<FunctionParameter>,
     body: OwnedReporter,
 }
 
 impl Function {
-    pub fn new(parent: Rc<RefCell<Scope>>, parameters: Vec<String>, body: Chunk<Reporter>) -> Self {
+    pub fn new(
+        engine: Rc<State>,
+        parent: Rc<RefCell<Scope>>,
+        parameters: Vec<Parameter>,
+        body: Chunk<Reporter>,
+    ) -> Self {
         Self {
+            engine,
             parent,
-            parameters,
+            parameters: parameters
+                .into_iter()
+                .map(|p| FunctionParameter {
+                    name: p.name.data.to_string(),
+                    default: p.default.map(OwnedReporter::new),
+                    rest: p.rest,
+                })
+                .collect(),
             body: OwnedReporter::new(body),
         }
     }
+
+    fn check_arity(&self, given: usize, span: Span) -> Result<()> {
+        let required = self
+            .parameters
+            .iter()
+            .filter(|p| p.default.is_none() && !p.rest)
+            .count();
+        let rest = self.parameters.last().is_some_and(|p| p.rest);
+        let positional = self.parameters.len() - rest as usize;
+        if given >= required && (rest || given <= positional) {
+            return Ok(());
+        }
+
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let expected = if rest {
+            format!("at least {required} argument{}", plural(required))
+        } else if required == positional {
+            format!("{required} argument{}", plural(required))
+        } else {
+            format!("{required} to {positional} arguments")
+        };
+        Err(Error::new(
+            format!("This function expects {expected}, but got {given}."),
+            span,
+        ))
+    }
+
+    /// Binds the arguments in a new scope and evaluates the body. A call in tail position
+    /// is handed back instead of being made, so [`Call::call_named`] can make it without
+    /// growing the stack.
+    fn enter(
+        &self,
+        args: Vec<Chunk<Value>>,
+        named: Vec<(Chunk<impl AsRef<str>>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Tail> {
+        let rest = self.parameters.last().is_some_and(|p| p.rest);
+        let positional = self.parameters.len() - rest as usize;
+        if named.is_empty() || (!rest && args.len() > positional) {
+            self.check_arity(args.len(), span)?;
+        }
+
+        let mut args = args.into_iter().map(|arg| arg.data);
+        let mut slots: Vec<Option<Value>> = args.by_ref().take(positional).map(Some).collect();
+        slots.resize(positional, None);
+        let mut rest_values: Vec<Value> = args.collect();
+
+        for (name, value) in named {
+            let index = self.parameters[..positional]
+                .iter()
+                .position(|p| p.name == name.data.as_ref())
+                .ok_or_else(|| {
+                    Error::new(
+                        format!(
+                            "This function has no parameter named '{}'.",
+                            name.data.as_ref()
+                        ),
+                        name.span,
+                    )
+                })?;
+            if slots[index].is_some() {
+                return Err(Error::new(
+                    format!(
+                        "The argument '{}' was given more than once.",
+                        name.data.as_ref()
+                    ),
+                    name.span,
+                ));
+            }
+            slots[index] = Some(value.data);
+        }
+
+        let scope = Scope::new_shared(self.engine.clone(), Some(self.parent.clone()));
+        let mut slots = slots.into_iter();
+        for parameter in &self.parameters {
+            let value = if parameter.rest {
+                self.engine.check_size(rest_values.len(), span)?;
+                Value::Array(std::mem::take(&mut rest_values))
+            } else if let Some(value) = slots.next().flatten() {
+                value
+            } else if let Some(default) = &parameter.default {
+                evaluate(&scope, default.borrow().clone())?
+            } else {
+                return Err(Error::new(
+                    format!("Missing argument '{}'.", parameter.name),
+                    span,
+                ));
+            };
+            scope
+                .borrow_mut()
+                .variables
+                .insert(parameter.name.clone(), value);
+        }
+        evaluate_tail(&scope, self.body.borrow().clone())
+    }
 }
 
 impl Call for Function {
-    fn call(&self, args: Vec<Value>, _span: Span) -> Result<Value> {
-        let mut scope = Scope::new(Some(self.parent.clone()));
-        for (name, value) in self.parameters.iter().zip(args.into_iter()) {
-            scope.variables.insert(name.to_string(), value);
+    fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+        self.call_named(args, Vec::new(), span)
+    }
+
+    fn parameter_names(&self) -> Option<Vec<&str>> {
+        Some(
+            self.parameters
+                .iter()
+                .filter(|p| !p.rest)
+                .map(|p| p.name.as_str())
+                .collect(),
+        )
+    }
+
+    fn call_named(
+        &self,
+        args: Vec<Chunk<Value>>,
+        named: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        let _frame = Frame::enter(&self.engine, span)?;
+        let mut tail = self.enter(args, named, span)?;
+        loop {
+            match tail {
+                Tail::Value(value) => return Ok(value),
+                Tail::Call {
+                    callable,
+                    args,
+                    named,
+                    span,
+                } => {
+                    tail = match callable.as_any().and_then(|f| f.downcast_ref::<Function>()) {
+                        Some(function) => function.enter(args, named, span)?,
+                        None => {
+                            let value =
+                                call_owned(&self.engine, callable.as_ref(), args, named, span)?;
+                            self.engine.check_value(&value, span)?;
+                            Tail::Value(value)
+                        }
+                    };
+                }
+            }
         }
-        evaluate(&Rc::new(RefCell::new(scope)), self.body.borrow().clone())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        visit(Edge::Map(&self.parent));
     }
 }
@@ -1,32 +1,52 @@
-use std::{cell::RefCell, collections::HashMap, fs, rc::Rc};
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, rc::Rc};
 
 use function::Function;
 use lib::{
+    compat::user_data::UserData,
     error::{Error, Result},
-    instruction::{Comparison, Instruction, Reporter},
-    module_registry::REGISTRY,
-    span::Chunk,
+    gc::{self, Edge},
+    instruction::{Argument, Instruction, ObjectEntry, Reporter},
+    span::{Chunk, Span},
     type_error,
-    value::{MapRef, Value},
+    value::{Call, MapRef, Value},
 };
 
-use crate::run;
+use crate::{engine::State, std_lib::types::RecordType};
 
 mod function;
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug)]
 pub struct Scope {
+    engine: Rc<State>,
     parent: Option<Rc<RefCell<dyn MapRef>>>,
     variables: HashMap<String, Value>,
 }
 
 impl Scope {
-    pub fn new(parent: Option<Rc<RefCell<dyn MapRef>>>) -> Self {
+    pub fn new(engine: Rc<State>, parent: Option<Rc<RefCell<dyn MapRef>>>) -> Self {
         Self {
+            engine,
             parent,
             variables: HashMap::new(),
         }
     }
+    /// Creates a scope that the cycle collector knows about, since functions defined in
+    /// it keep it alive.
+    pub fn new_shared(
+        engine: Rc<State>,
+        parent: Option<Rc<RefCell<dyn MapRef>>>,
+    ) -> Rc<RefCell<Self>> {
+        let scope = Rc::new(RefCell::new(Self::new(engine, parent)));
+        gc::track(&scope.borrow().engine.tracker, &scope);
+        scope
+    }
+    /// Creates a scope nested inside another one, belonging to the same engine.
+    pub fn child(parent: &Rc<RefCell<Scope>>) -> Rc<RefCell<Self>> {
+        let engine = parent.borrow().engine.clone();
+        Self::new_shared(engine, Some(parent.clone()))
+    }
     fn up(rc: &Rc<RefCell<Scope>>, i: usize) -> Option<Rc<RefCell<dyn MapRef>>> {
         fn up_dyn(rc: &Rc<RefCell<dyn MapRef>>, i: usize) -> Option<Rc<RefCell<dyn MapRef>>> {
             if i == 0 {
@@ -46,8 +66,9 @@ impl MapRef for Scope {
     fn get(&self, name: &str) -> Option<&Value> {
         self.variables.get(name)
     }
-    fn set(&mut self, name: String, val: Value) {
+    fn set(&mut self, name: String, val: Value) -> Result<()> {
         self.variables.insert(name, val);
+        Ok(())
     }
     fn parent(&self) -> Option<Rc<RefCell<dyn MapRef>>> {
         self.parent.clone()
@@ -55,22 +76,100 @@ impl MapRef for Scope {
     fn as_hashmap(&self) -> Option<&HashMap<String, Value>> {
         Some(&self.variables)
     }
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        if let Some(parent) = &self.parent {
+            visit(Edge::Map(parent));
+        }
+        self.variables.values().for_each(|value| value.trace(visit));
+    }
+    fn clear(&mut self) {
+        self.parent = None;
+        self.variables.clear();
+    }
+}
+
+/// The result of evaluating a reporter in tail position. A call there is handed back
+/// instead of being made, so that a function returning it can reuse its own frame.
+pub enum Tail {
+    Value(Value),
+    Call {
+        callable: Rc<dyn Call>,
+        args: Vec<Chunk<Value>>,
+        named: Vec<(Chunk<String>, Chunk<Value>)>,
+        span: Span,
+    },
+}
+
+impl Tail {
+    /// Makes the pending call, if there is one.
+    pub fn resolve(self, engine: &State) -> Result<Value> {
+        match self {
+            Self::Value(value) => Ok(value),
+            Self::Call {
+                callable,
+                args,
+                named,
+                span,
+            } => {
+                let value = call_owned(engine, callable.as_ref(), args, named, span)?;
+                engine.check_value(&value, span)?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+fn call_owned(
+    engine: &State,
+    callable: &dyn Call,
+    args: Vec<Chunk<Value>>,
+    named: Vec<(Chunk<String>, Chunk<Value>)>,
+    span: Span,
+) -> Result<Value> {
+    let (names, values): (Vec<_>, Vec<_>) = named.into_iter().unzip();
+    let named = names
+        .iter()
+        .map(|name| Chunk::new(name.data.as_str(), name.span))
+        .zip(values)
+        .collect();
+    callable.call_from(engine, args, named, span)
 }
 
 pub fn run_block(
     scope: &Rc<RefCell<Scope>>,
     instructions: Vec<Chunk<Instruction<'_>>>,
 ) -> Result<Value> {
+    let engine = scope.borrow().engine.clone();
+    run_block_tail(scope, instructions)?.resolve(&engine)
+}
+
+fn run_block_tail(
+    scope: &Rc<RefCell<Scope>>,
+    instructions: Vec<Chunk<Instruction<'_>>>,
+) -> Result<Tail> {
     let mut return_value = None;
     for instruction in instructions {
         match instruction.data {
-            Instruction::Set { map, name, value } => {
+            Instruction::Set {
+                map, name, value, ..
+            } => {
+                let map_span = map.span;
                 let map = evaluate(scope, map)?;
                 let value = evaluate(scope, value)?;
+                let engine = scope.borrow().engine.clone();
+                engine.check_value(&value, instruction.span)?;
                 match map {
-                    Value::MapRef(rc) => rc.borrow_mut().set(name.data.to_string(), value),
+                    Value::MapRef(rc) => {
+                        rc.borrow_mut()
+                            .set(name.data.to_string(), value)
+                            .map_err(|e| e.with_span(name.span))?;
+                        engine.check_value(&Value::MapRef(rc), name.span)?;
+                    }
+                    Value::UserData(data) => data.set(name.data, value, name.span)?,
                     Value::Map(_) => (), // its useless to insert a item into an owned map.
-                    _ => todo!(),
+                    map => {
+                        return Err(Error::new(type_error!("map", map.type_of()), map_span));
+                    }
                 };
             }
             Instruction::While { condition, body } => {
@@ -83,7 +182,7 @@ pub fn run_block(
                         ));
                     }
                 } {
-                    let new_scope = Rc::new(RefCell::new(Scope::new(Some(scope.clone()))));
+                    let new_scope = Scope::child(scope);
                     run_block(&new_scope, body.data.clone())?;
                 }
             }
@@ -112,7 +211,8 @@ pub fn run_block(
                 let name = name.data;
                 let mut key = name.to_string();
                 while !matches!(item, Value::Null) {
-                    let new_scope = Rc::new(RefCell::new(Scope::new(Some(scope.clone()))));
+                    scope.borrow().engine.step(iter_span)?;
+                    let new_scope = Scope::child(scope);
                     new_scope.borrow_mut().variables.insert(key, item);
                     run_block(&new_scope, body.data.clone())?;
                     item = next()?;
@@ -125,7 +225,7 @@ pub fn run_block(
                 }
             }
             Instruction::Return(reporter) => {
-                return_value = Some(evaluate(scope, reporter)?);
+                return_value = Some(evaluate_tail(scope, reporter)?);
                 break;
             }
             Instruction::Void(reporter) => {
@@ -133,86 +233,20 @@ pub fn run_block(
             }
         }
     }
-    Ok(return_value.unwrap_or_else(move || Value::MapRef(scope.clone())))
+    Ok(return_value.unwrap_or_else(move || Tail::Value(Value::MapRef(scope.clone()))))
 }
 
-pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Result<Value> {
+/// Evaluates a reporter in tail position, where calls are left for the caller to make.
+pub fn evaluate_tail(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Result<Tail> {
     match reporter.data {
-        Reporter::Parent(up) => Ok(Value::MapRef(Scope::up(scope, up).ok_or_else(|| {
-            dbg!(scope);
-            dbg!(up);
-            Error::new("Failed to get parent scope", reporter.span)
-        })?)),
-        Reporter::Null => Ok(Value::Null),
-        Reporter::ConstStr(str) => Ok(Value::String(str.to_string())),
-        Reporter::ConstInt(int) => Ok(Value::Int(int)),
-        Reporter::ConstFloat(float) => Ok(Value::Float(float)),
-        Reporter::ConstBool(bool) => Ok(Value::Boolean(bool)),
-        Reporter::Block(chunks) => {
-            let scope = Scope::new(Some(scope.clone()));
-            run_block(&Rc::new(RefCell::new(scope)), chunks)
-        }
-        Reporter::Array(items) => Ok(Value::Array(
-            items
-                .data
-                .into_iter()
-                .map(|c| evaluate(scope, c))
-                .collect::<Result<_>>()?,
-        )),
-        Reporter::Function { parameters, body } => Ok(Value::Function(Rc::new(Function::new(
-            scope.clone(),
-            parameters.iter().map(|s| s.data.to_string()).collect(),
-            body.unbox(),
-        )))),
-        Reporter::Get { map, name } => {
-            let map_span = map.span;
-            match evaluate(scope, map.unbox())? {
-                Value::Map(map) => map.get(name.data).cloned(),
-                Value::MapRef(map) => map.borrow().get(name.data).cloned(),
-                v => return Err(Error::new(type_error!("map", v.type_of()), map_span)),
-            }
-            .ok_or_else(|| Error::new(format!("No property '{}'.", name.data), name.span))
-        }
-        Reporter::DynGet { map, attr } => {
-            let map_span = map.span;
-            let attr_span = attr.span;
-            let name = match evaluate(scope, attr.unbox())? {
-                Value::String(str) => str,
-                v => return Err(Error::new(type_error!("string", v.type_of()), attr_span)),
-            };
-            match evaluate(scope, map.unbox())? {
-                Value::Map(map) => map.get(&name).cloned(),
-                Value::MapRef(map) => map.borrow().get(&name).cloned(),
-                v => return Err(Error::new(type_error!("map", v.type_of()), map_span)),
-            }
-            .ok_or_else(|| Error::new(format!("No property '{}'.", name), attr_span))
-        }
-        Reporter::Call(func, parameters) => {
+        Reporter::Block(chunks) => run_block_tail(&Scope::child(scope), chunks),
+        Reporter::Call(func, arguments) => {
             let func_span = func.span;
-            let callable = match evaluate(scope, func.unbox())? {
-                Value::Function(c) => c,
-                v => return Err(Error::new(type_error!("function", v.type_of()), func_span)),
+            let Some(callable) = evaluate_chain(scope, func.unbox())? else {
+                return Ok(Tail::Value(Value::Null));
             };
-            let parameters = parameters
-                .into_iter()
-                .map(|p| evaluate(scope, p))
-                .collect::<Result<Vec<_>>>()?;
-            callable.call(parameters, reporter.span)
+            call_tail(scope, callable, func_span, arguments, reporter.span)
         }
-        Reporter::Import(path) => REGISTRY.with_borrow_mut(|registry| {
-            registry.get_or_initialize(path.data, |path| {
-                let code = fs::read_to_string(&path).map_err(|_| {
-                    Error::new(
-                        format!(
-                            "The module path '{path}' does not exist.",
-                            path = path.to_str().unwrap_or_default()
-                        ),
-                        reporter.span,
-                    )
-                })?;
-                run(&code)
-            })
-        }),
         Reporter::If { blocks, else_block } => {
             for (cond, body) in blocks {
                 let cond_span = cond.span;
@@ -220,20 +254,118 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
                     Value::Boolean(b) => b,
                     v => return Err(Error::new(type_error!("boolean", v.type_of()), cond_span)),
                 } {
-                    return evaluate(scope, body);
+                    return evaluate_tail(scope, body);
                 }
             }
             if let Some(body) = else_block {
-                evaluate(scope, body.unbox())
+                evaluate_tail(scope, body.unbox())
             } else {
-                Ok(Value::Null)
+                Ok(Tail::Value(Value::Null))
+            }
+        }
+        data => evaluate(scope, Chunk::new(data, reporter.span)).map(Tail::Value),
+    }
+}
+
+pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Result<Value> {
+    scope.borrow().engine.step(reporter.span)?;
+    match reporter.data {
+        Reporter::Parent(up) => {
+            Ok(Value::MapRef(Scope::up(scope, up).ok_or_else(|| {
+                Error::new("Failed to get parent scope", reporter.span)
+            })?))
+        }
+        Reporter::Null => Ok(Value::Null),
+        Reporter::ConstStr(str) => Ok(Value::String(str.to_string())),
+        Reporter::ConstInt(int) => Ok(Value::Int(int)),
+        Reporter::ConstFloat(float) => Ok(Value::Float(float)),
+        Reporter::ConstBool(bool) => Ok(Value::Boolean(bool)),
+        data @ (Reporter::Block(_) | Reporter::Call(..) | Reporter::If { .. }) => {
+            let engine = scope.borrow().engine.clone();
+            evaluate_tail(scope, Chunk::new(data, reporter.span))?.resolve(&engine)
+        }
+        Reporter::Array(items) => {
+            scope
+                .borrow()
+                .engine
+                .check_size(items.data.len(), items.span)?;
+            Ok(Value::Array(
+                items
+                    .data
+                    .into_iter()
+                    .map(|c| evaluate(scope, c))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        Reporter::Object(entries) => {
+            let mut map = HashMap::new();
+            for entry in entries {
+                let span = entry.span;
+                match entry.data {
+                    ObjectEntry::Field(key, value) => {
+                        map.insert(key.data.to_string(), evaluate(scope, value)?);
+                    }
+                    ObjectEntry::Computed(key, value) => {
+                        let key_span = key.span;
+                        let key = match evaluate(scope, key)? {
+                            Value::String(str) => str,
+                            v => {
+                                return Err(Error::new(
+                                    type_error!("string", v.type_of()),
+                                    key_span,
+                                ));
+                            }
+                        };
+                        map.insert(key, evaluate(scope, value)?);
+                    }
+                    ObjectEntry::Spread(value) => match evaluate(scope, Chunk::new(value, span))? {
+                        Value::Map(other) => map.extend(other),
+                        Value::MapRef(other) => {
+                            let other = other.borrow();
+                            let Some(other) = other.as_hashmap() else {
+                                return Err(Error::new("This map can't be spread.", span));
+                            };
+                            map.extend(other.iter().map(|(k, v)| (k.clone(), v.clone())));
+                        }
+                        v => return Err(Error::new(type_error!("map", v.type_of()), span)),
+                    },
+                }
             }
+            scope.borrow().engine.check_size(map.len(), reporter.span)?;
+            let map = Rc::new(RefCell::new(map));
+            gc::track(&scope.borrow().engine.tracker, &map);
+            Ok(Value::MapRef(map))
+        }
+        Reporter::Function {
+            parameters, body, ..
+        } => Ok(Value::Function(Rc::new(Function::new(
+            scope.borrow().engine.clone(),
+            scope.clone(),
+            parameters,
+            body.unbox(),
+        )))),
+        Reporter::RecordType { name, fields } => Ok(Value::Function(RecordType::new(
+            name.data.to_string(),
+            fields
+                .into_iter()
+                .map(|(field, ty)| (field.data.to_string(), ty.data.to_string()))
+                .collect(),
+        ))),
+        data @ (Reporter::Get { .. } | Reporter::DynGet { .. }) => {
+            Ok(evaluate_chain(scope, Chunk::new(data, reporter.span))?.unwrap_or(Value::Null))
+        }
+        Reporter::Import(path) => {
+            let engine = scope.borrow().engine.clone();
+            engine.import(path.data, reporter.span)
         }
         Reporter::Add { a, b } => {
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
                 Value::Int(int) => match evaluate(scope, b.unbox())? {
-                    Value::Int(b) => Ok(Value::Int(int + b)),
+                    Value::Int(b) => int
+                        .checked_add(b)
+                        .map(Value::Int)
+                        .ok_or_else(|| overflow(reporter.span)),
                     Value::Float(_) => Err(Error::new(
                         "You cannot do arithmetic with floats and integers.",
                         b_span,
@@ -255,7 +387,10 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
                 Value::Int(int) => match evaluate(scope, b.unbox())? {
-                    Value::Int(b) => Ok(Value::Int(int - b)),
+                    Value::Int(b) => int
+                        .checked_sub(b)
+                        .map(Value::Int)
+                        .ok_or_else(|| overflow(reporter.span)),
                     Value::Float(_) => Err(Error::new(
                         "You cannot do arithmetic with floats and integers.",
                         b_span,
@@ -277,7 +412,10 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
                 Value::Int(int) => match evaluate(scope, b.unbox())? {
-                    Value::Int(b) => Ok(Value::Int(int * b)),
+                    Value::Int(b) => int
+                        .checked_mul(b)
+                        .map(Value::Int)
+                        .ok_or_else(|| overflow(reporter.span)),
                     Value::Float(_) => Err(Error::new(
                         "You cannot do arithmetic with floats and integers.",
                         b_span,
@@ -299,7 +437,11 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
                 Value::Int(int) => match evaluate(scope, b.unbox())? {
-                    Value::Int(b) => Ok(Value::Int(int / b)),
+                    Value::Int(0) => Err(Error::new("Cannot divide by zero.", b_span)),
+                    Value::Int(b) => int
+                        .checked_div(b)
+                        .map(Value::Int)
+                        .ok_or_else(|| overflow(reporter.span)),
                     Value::Float(_) => Err(Error::new(
                         "You cannot do arithmetic with floats and integers.",
                         b_span,
@@ -321,7 +463,10 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
                 Value::Int(int) => match evaluate(scope, b.unbox())? {
-                    Value::Int(b) => Ok(Value::Int(int.pow(b.try_into().unwrap_or(0)))),
+                    Value::Int(b) => int
+                        .checked_pow(b.try_into().unwrap_or(0))
+                        .map(Value::Int)
+                        .ok_or_else(|| overflow(reporter.span)),
                     Value::Float(_) => Err(Error::new(
                         "You cannot do arithmetic with floats and integers.",
                         b_span,
@@ -349,8 +494,9 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
         Reporter::And { a, b } => {
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
-                Value::Boolean(a) => match evaluate(scope, b.unbox())? {
-                    Value::Boolean(b) => Ok(Value::Boolean(a && b)),
+                Value::Boolean(false) => Ok(Value::Boolean(false)),
+                Value::Boolean(true) => match evaluate(scope, b.unbox())? {
+                    Value::Boolean(b) => Ok(Value::Boolean(b)),
                     v => Err(Error::new(type_error!("boolean", v.type_of()), b_span)),
                 },
                 v => Err(Error::new(type_error!("boolean", v.type_of()), a_span)),
@@ -359,13 +505,18 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
         Reporter::Or { a, b } => {
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
-                Value::Boolean(a) => match evaluate(scope, b.unbox())? {
-                    Value::Boolean(b) => Ok(Value::Boolean(a || b)),
+                Value::Boolean(true) => Ok(Value::Boolean(true)),
+                Value::Boolean(false) => match evaluate(scope, b.unbox())? {
+                    Value::Boolean(b) => Ok(Value::Boolean(b)),
                     v => Err(Error::new(type_error!("boolean", v.type_of()), b_span)),
                 },
                 v => Err(Error::new(type_error!("boolean", v.type_of()), a_span)),
             }
         }
+        Reporter::Coalesce { a, b } => match evaluate(scope, a.unbox())? {
+            Value::Null => evaluate(scope, b.unbox()),
+            value => Ok(value),
+        },
         Reporter::Equality { a, b } => Ok(Value::Boolean(
             evaluate(scope, a.unbox())? == evaluate(scope, b.unbox())?,
         )),
@@ -373,19 +524,11 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
             let (a_span, b_span) = (a.span, b.span);
             match evaluate(scope, a.unbox())? {
                 Value::Int(a) => match evaluate(scope, b.unbox())? {
-                    Value::Int(b) => Ok(Value::Boolean(if op.data == Comparison::LessThan {
-                        a < b
-                    } else {
-                        a > b
-                    })),
+                    Value::Int(b) => Ok(Value::Boolean(op.data.compare(a, b))),
                     v => Err(Error::new(type_error!("integer", v.type_of()), b_span)),
                 },
                 Value::Float(a) => match evaluate(scope, b.unbox())? {
-                    Value::Float(b) => Ok(Value::Boolean(if op.data == Comparison::LessThan {
-                        a < b
-                    } else {
-                        a > b
-                    })),
+                    Value::Float(b) => Ok(Value::Boolean(op.data.compare(a, b))),
                     v => Err(Error::new(type_error!("float", v.type_of()), b_span)),
                 },
                 v => Err(Error::new(type_error!("number", v.type_of()), a_span)),
@@ -401,10 +544,132 @@ pub fn evaluate(scope: &Rc<RefCell<Scope>>, reporter: Chunk<Reporter<'_>>) -> Re
         Reporter::Negative(value) => {
             let a_span = value.span;
             match evaluate(scope, value.unbox())? {
-                Value::Int(a) => Ok(Value::Int(-a)),
+                Value::Int(a) => a
+                    .checked_neg()
+                    .map(Value::Int)
+                    .ok_or_else(|| overflow(reporter.span)),
                 Value::Float(a) => Ok(Value::Float(-a)),
-                v => Err(Error::new(type_error!("boolean", v.type_of()), a_span)),
+                v => Err(Error::new(type_error!("number", v.type_of()), a_span)),
+            }
+        }
+    }
+}
+
+/// The error for int arithmetic whose result doesn't fit in an int.
+fn overflow(span: Span) -> Error {
+    Error::new("The result is too large to fit in an int.", span)
+}
+
+/// Evaluates the arguments of a call to `callable`, handing the call back to be made.
+fn call_tail(
+    scope: &Rc<RefCell<Scope>>,
+    callable: Value,
+    func_span: Span,
+    arguments: Vec<Chunk<Argument<'_>>>,
+    span: Span,
+) -> Result<Tail> {
+    let callable = match callable {
+        Value::Function(c) => c,
+        v => return Err(Error::new(type_error!("function", v.type_of()), func_span)),
+    };
+    let mut args = Vec::new();
+    let mut named = Vec::new();
+    for argument in arguments {
+        let span = argument.span;
+        match argument.data {
+            Argument::Positional(value) => {
+                args.push(Chunk::new(evaluate(scope, Chunk::new(value, span))?, span))
+            }
+            Argument::Spread(value) => match evaluate(scope, Chunk::new(value, span))? {
+                Value::Array(items) => {
+                    args.extend(items.into_iter().map(|item| Chunk::new(item, span)))
+                }
+                v => return Err(Error::new(type_error!("array", v.type_of()), span)),
+            },
+            Argument::Named(name, value) => {
+                let value_span = value.span;
+                named.push((
+                    Chunk::new(name.data.to_string(), name.span),
+                    Chunk::new(evaluate(scope, value)?, value_span),
+                ));
             }
         }
     }
+    Ok(Tail::Call {
+        callable,
+        args,
+        named,
+        span,
+    })
+}
+
+/// Evaluates a link in a chain of property gets and calls, returning `None` if an
+/// earlier `?.` found null. The rest of the chain is then skipped, so `x?.a.b` and
+/// `x?.f().b` are null when `x` is.
+fn evaluate_chain(
+    scope: &Rc<RefCell<Scope>>,
+    reporter: Chunk<Reporter<'_>>,
+) -> Result<Option<Value>> {
+    enum Key<'a> {
+        Name(Chunk<&'a str>),
+        Dyn(Chunk<Box<Reporter<'a>>>),
+    }
+    let (map, key, optional) = match reporter.data {
+        Reporter::Get {
+            map,
+            name,
+            optional,
+        } => (map, Key::Name(name), optional),
+        Reporter::DynGet {
+            map,
+            attr,
+            optional,
+        } => (map, Key::Dyn(attr), optional),
+        Reporter::Call(func, arguments) => {
+            let func_span = func.span;
+            let Some(callable) = evaluate_chain(scope, func.unbox())? else {
+                return Ok(None);
+            };
+            let engine = scope.borrow().engine.clone();
+            return call_tail(scope, callable, func_span, arguments, reporter.span)?
+                .resolve(&engine)
+                .map(Some);
+        }
+        data => return evaluate(scope, Chunk::new(data, reporter.span)).map(Some),
+    };
+
+    let map_span = map.span;
+    let map = match *map.data {
+        Reporter::Get { .. } | Reporter::DynGet { .. } => {
+            scope.borrow().engine.step(map_span)?;
+            evaluate_chain(scope, map.unbox())?
+        }
+        Reporter::Call(..) => evaluate_chain(scope, map.unbox())?,
+        _ => Some(evaluate(scope, map.unbox())?),
+    };
+    let map = match map {
+        Some(Value::Null) if optional => return Ok(None),
+        Some(map) => map,
+        None => return Ok(None),
+    };
+    let (name, name_span): (Cow<str>, Span) = match key {
+        Key::Name(name) => (Cow::Borrowed(name.data), name.span),
+        Key::Dyn(attr) => {
+            let attr_span = attr.span;
+            match evaluate(scope, attr.unbox())? {
+                Value::String(str) => (Cow::Owned(str), attr_span),
+                v => return Err(Error::new(type_error!("string", v.type_of()), attr_span)),
+            }
+        }
+    };
+
+    match map {
+        Value::Map(map) => map.get(name.as_ref()).cloned(),
+        Value::MapRef(map) => map.borrow().get(&name).cloned(),
+        Value::UserData(data) => UserData::get(&data, &name, map_span)?,
+        v => return Err(Error::new(type_error!("map", v.type_of()), map_span)),
+    }
+    .or(optional.then_some(Value::Null))
+    .map(Some)
+    .ok_or_else(|| Error::new(format!("No property '{name}'."), name_span))
 }
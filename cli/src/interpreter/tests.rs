@@ -0,0 +1,675 @@
+use std::thread;
+
+use lib::{
+    FolioseType,
+    compat::{context::Context, function::IntoCallable, user_data::UserType},
+    error::{ErrorKind, Limit, Result},
+    methods,
+    span::Span,
+    value::Value,
+};
+
+use crate::engine::{Engine, Limits};
+
+fn run(code: &str) -> Result<Value> {
+    Engine::new().run(code)
+}
+
+fn error(code: &str) -> String {
+    format!("{:?}", run(code).expect_err("expected the script to fail"))
+}
+
+#[test]
+fn and_skips_the_right_side_when_the_left_is_false() {
+    let value = run("
+        calls = 0;
+        touch = fn() -> {
+            calls += 1;
+            return true;
+        };
+        result = false && touch();
+        return [result, calls];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![Value::Boolean(false), Value::Int(0)])
+    );
+}
+
+#[test]
+fn or_skips_the_right_side_when_the_left_is_true() {
+    let value = run("
+        calls = 0;
+        touch = fn() -> {
+            calls += 1;
+            return false;
+        };
+        result = true || touch();
+        return [result, calls];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![Value::Boolean(true), Value::Int(0)])
+    );
+}
+
+#[test]
+fn right_side_is_evaluated_when_it_decides_the_result() {
+    let value = run("return [true && false, false || true, true && true];");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![
+            Value::Boolean(false),
+            Value::Boolean(true),
+            Value::Boolean(true)
+        ])
+    );
+}
+
+#[test]
+fn skipped_right_side_guards_against_null() {
+    let value = run("
+        settings = null;
+        return settings != null && settings.ready;
+    ");
+    assert_eq!(value.unwrap(), Value::Boolean(false));
+}
+
+#[test]
+fn logical_operators_reject_non_boolean_operands() {
+    assert!(error("return 1 && true;").contains("Expected boolean"));
+    assert!(error("return false || \"yes\";").contains("Expected boolean"));
+    assert!(error("return null || true;").contains("Expected boolean"));
+}
+
+#[test]
+fn conditions_reject_non_boolean_values() {
+    assert!(error("if 1 { return 1; }").contains("Expected boolean"));
+    assert!(error("while null { }").contains("Expected boolean"));
+}
+
+#[test]
+fn operators_on_the_same_level_group_from_the_left() {
+    let value = run("return [10 - 2 + 3, 8 / 4 * 2, 2 + 3 * 4 - 1];");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![Value::Int(11), Value::Int(4), Value::Int(13)])
+    );
+}
+
+#[test]
+fn pipeline_needs_its_two_characters_together() {
+    assert_eq!(run("return 1 |> str();").unwrap(), Value::from("1"));
+    assert!(error("return 1 | > str();").contains("Expected '|>'"));
+}
+
+#[test]
+fn pipeline_binds_looser_than_coalesce() {
+    // `(null ?? 2) |> str()`, not `null ?? (2 |> str())`.
+    assert_eq!(run("return null ?? 2 |> str();").unwrap(), Value::from("2"));
+    // `1 |> (missing ?? str)`.
+    assert_eq!(
+        run("missing = null; return 1 |> missing ?? str;").unwrap(),
+        Value::from("1")
+    );
+    assert_eq!(run("return 1 + 2 |> str();").unwrap(), Value::from("3"));
+}
+
+/// Runs `code` on a thread with the same stack as a usual main thread.
+fn run_on_main_sized_stack(limits: Limits, code: &'static str) -> Option<ErrorKind> {
+    thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(move || {
+            let engine = Engine::new();
+            engine.set_limits(limits);
+            engine.run(code).err().map(|e| e.kind())
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+const DEEP: &str = "
+    depth = fn(n) -> if n == 0 -> 0; else -> 1 + depth(n - 1);;
+    return depth(100000);
+";
+
+#[test]
+fn default_limits_stop_deep_recursion_before_the_stack_overflows() {
+    let kind = run_on_main_sized_stack(Limits::default(), DEEP);
+    assert!(matches!(kind, Some(ErrorKind::LimitExceeded(_))));
+}
+
+#[test]
+fn stack_limit_applies_when_the_depth_limit_is_raised() {
+    let limits = Limits {
+        max_depth: usize::MAX,
+        ..Limits::default()
+    };
+    let kind = run_on_main_sized_stack(limits, DEEP);
+    assert_eq!(kind, Some(ErrorKind::LimitExceeded(Limit::Stack)));
+}
+
+#[test]
+fn gc_counts_only_the_maps_it_clears() {
+    let collected = run("
+        gc = import(\"std/gc\");
+        make = fn() -> {
+            node = #{};
+            node.get = fn() -> node;
+            return null;
+        };
+        make();
+        return gc.collect().collected;
+    ")
+    .unwrap();
+    // `node`, the scope of `make` and the scope the function was defined in, but not the
+    // function that closes the cycle.
+    assert_eq!(collected, Value::from(3));
+}
+
+#[test]
+fn gc_only_collects_maps_from_its_own_engine() {
+    let other = Engine::new();
+    other
+        .run("node = #{}; node.self = node; return null;")
+        .unwrap();
+    let collected = run("return import(\"std/gc\").collect().collected;").unwrap();
+    assert_eq!(collected, Value::from(0));
+}
+
+#[test]
+fn interrupts_sent_while_idle_are_ignored() {
+    let engine = Engine::new();
+    engine.interrupt_handle().interrupt();
+    assert_eq!(engine.run("return 1;").unwrap(), Value::from(1));
+}
+
+/// Warns about each value it is given, and returns it.
+fn noisy(ctx: &Context, value: Value, _label: Option<String>) -> Result<Value> {
+    ctx.warn(format!("saw {value}"), ctx.span());
+    Ok(value)
+}
+
+/// Warns about each value it is given, and keeps all of them.
+fn keep(ctx: &Context, value: Value) -> Result<bool> {
+    ctx.warn(format!("kept {value}"), ctx.span());
+    Ok(true)
+}
+
+fn run_with_noisy(code: &str) -> (Result<Value>, usize) {
+    let engine = Engine::new();
+    engine.register_module("noisy", noisy.into_callable());
+    engine.register_module("keep", keep.into_callable());
+    let result = engine.run(code);
+    (result, engine.take_warnings().len())
+}
+
+#[test]
+fn callbacks_can_have_trailing_optional_parameters() {
+    let (result, _) = run_with_noisy(
+        "iter = import(\"std/iter\"); return iter.collect(iter.map([1, 2], import(\"noisy\")));",
+    );
+    assert_eq!(result.unwrap(), Value::from(vec![1, 2]));
+}
+
+#[test]
+fn callbacks_from_iterators_reach_the_engine() {
+    let (result, warnings) = run_with_noisy(
+        "
+        iter = import(\"std/iter\");
+        return iter.map([1, 2, 3], import(\"noisy\"))
+            |> iter.filter(import(\"keep\"))
+            |> iter.collect();
+    ",
+    );
+    assert_eq!(result.unwrap(), Value::from(vec![1, 2, 3]));
+    assert_eq!(warnings, 6);
+}
+
+#[test]
+fn wide_ints_that_dont_fit_fail_when_returned() {
+    let engine = Engine::new();
+    engine.register_module("big", (|| Ok(i64::MAX)).into_callable());
+    engine.register_module("small", (|| Ok(7usize)).into_callable());
+    let error = engine.run("return import(\"big\")();").unwrap_err();
+    assert_ne!(error.span(), Span::default());
+    assert!(format!("{error:?}").contains("doesn't fit in an int"));
+    assert_eq!(
+        engine.run("return import(\"small\")();").unwrap(),
+        Value::from(7)
+    );
+}
+
+#[derive(FolioseType)]
+#[foliose(crate = "lib")]
+struct Counter {
+    count: i32,
+}
+
+#[methods(crate = "lib")]
+impl Counter {
+    pub fn new(count: i32) -> Result<Counter> {
+        Ok(Counter { count })
+    }
+    pub fn add(&mut self, by: i32) -> Result<i32> {
+        self.count += by;
+        Ok(self.count)
+    }
+    #[foliose(name = "twice")]
+    pub fn doubled(&self) -> Result<i32> {
+        Ok(self.count * 2)
+    }
+}
+
+#[test]
+fn derived_methods_are_found_by_name() {
+    let engine = Engine::new();
+    engine.register_module("counter", Counter::module());
+    let result = engine.run(
+        "
+        counter = import(\"counter\").new(1);
+        counter.add(2);
+        return [counter.count, counter.twice()];
+    ",
+    );
+    assert_eq!(result.unwrap(), Value::from(vec![3, 6]));
+}
+
+#[test]
+fn user_data_fields_check_their_types() {
+    let engine = Engine::new();
+    engine.register_module("counter", Counter::module());
+    let error = engine
+        .run("counter = import(\"counter\").new(1); counter.count = \"many\";")
+        .unwrap_err();
+    assert!(format!("{error:?}").contains("int"), "{error:?}");
+    let error = engine
+        .run("counter = import(\"counter\").new(1); counter.missing = 1;")
+        .unwrap_err();
+    assert!(
+        format!("{error:?}").contains("The type Counter has no property 'missing'."),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn collecting_an_iterator_respects_the_size_limit() {
+    let engine = Engine::new();
+    engine.set_limits(Limits {
+        max_collection_size: Some(10),
+        ..Limits::default()
+    });
+    let kind = engine
+        .run("iter = import(\"std/iter\"); return iter.collect(iter.range(0, 30000000));")
+        .err()
+        .map(|e| e.kind());
+    assert_eq!(kind, Some(ErrorKind::LimitExceeded(Limit::CollectionSize)));
+}
+
+#[test]
+fn iterator_adaptors_count_steps() {
+    let limits = Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    };
+    let code = "
+        iter = import(\"std/iter\");
+        keep = import(\"keep\");
+        return iter.range(0, 30000000) |> iter.filter(keep) |> iter.map(keep) |> iter.collect();
+    ";
+    let engine = Engine::new();
+    engine.set_limits(limits);
+    engine.register_module("keep", keep.into_callable());
+    let kind = engine.run(code).err().map(|e| e.kind());
+    assert_eq!(kind, Some(ErrorKind::LimitExceeded(Limit::Steps)));
+}
+
+#[test]
+fn setting_a_field_on_a_non_map_is_a_type_error() {
+    let message = error("x = 5; x.y = 1;");
+    assert!(message.contains("map"), "{message}");
+    assert!(error("\"text\".length = 1;").contains("map"));
+}
+
+#[test]
+fn iterator_errors_point_at_the_argument() {
+    for call in ["collect(5)", "map(5, str)", "filter(5, str)"] {
+        let code = format!("iter = import(\"std/iter\"); return iter.{call};");
+        let span = run(&code).unwrap_err().span();
+        assert_eq!(span.start.col, code.find('5').unwrap(), "{call}");
+    }
+}
+
+#[test]
+fn only_records_of_the_type_are_of_the_record_type() {
+    let value = run("
+        type Point {x: int, y: int}
+        type Size {x: int, y: int}
+        p = Point(1, 2);
+        copy = #{...p};
+        literal = #{__type: \"Point\", __constructor: Point, x: 1, y: 2};
+        return [type.is(p, Point), type.is(p, Size), type.is(copy, Point), type.is(literal, Point)];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Boolean(false),
+            Value::Boolean(false),
+        ])
+    );
+}
+
+#[test]
+fn checking_against_a_function_that_isnt_a_record_type_is_an_error() {
+    assert!(error("return type.is(1, str);").contains("Expected a type name or record type"));
+}
+
+#[test]
+fn record_fields_must_have_a_known_type() {
+    assert!(error("type Point {x: integer, y: int}").contains("Unknown type 'integer'."));
+    let value = run("
+        type Point {x: int, y: int}
+        type Line {from: Point, to: Point}
+        type Node {value: int, next: Node}
+        return Line(Point(0, 0), Point(1, 1)).to.x;
+    ");
+    assert_eq!(value.unwrap(), Value::Int(1));
+}
+
+#[test]
+fn str_writes_any_value() {
+    let value = run("return [str(1.5), str([1, \"a\"]), str(#{b: true, a: null}), str(str)];");
+    assert_eq!(
+        value.unwrap(),
+        Value::from(vec![
+            "1.5",
+            "[1, \"a\"]",
+            "{ a: null, b: true }",
+            "<function>"
+        ])
+    );
+    assert!(run("println([1.5, #{a: 1}]);").is_ok());
+}
+
+#[test]
+fn str_uses_str_overrides() {
+    let value = run("
+        point = #{x: 1, __str: fn(p) -> \"(\" .. str(p.x) .. \")\"};
+        return [str(point), str([point])];
+    ");
+    assert_eq!(value.unwrap(), Value::from(vec!["(1)", "[(1)]"]));
+}
+
+#[test]
+fn a_str_override_that_calls_str_on_itself_is_an_error() {
+    let message = error("point = #{__str: fn(p) -> str(p)}; return str(point);");
+    assert!(
+        message.contains("converts the value itself to a string"),
+        "{message}"
+    );
+}
+
+#[test]
+fn native_argument_errors_point_at_the_argument() {
+    let code = "return int.to_str(1.5);";
+    let error = run(code).unwrap_err();
+    assert_eq!(error.span().start.col, code.find("1.5").unwrap());
+    assert!(format!("{error:?}").contains("Expected int, but instead found float"));
+}
+
+#[test]
+fn default_parameters_fill_in_missing_arguments() {
+    let value = run("
+        greet = fn(name, greeting = \"Hello\") -> greeting .. \" \" .. name;
+        scaled = fn(x, factor = x * 2) -> x * factor;
+        return [greet(\"you\"), greet(\"you\", \"Hi\"), str(scaled(3)), str(scaled(3, 1))];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::from(vec!["Hello you", "Hi you", "18", "3"])
+    );
+}
+
+#[test]
+fn rest_parameters_collect_the_remaining_arguments() {
+    let value = run("
+        gather = fn(first, ...rest) -> [first, rest];
+        return [gather(1), gather(1, 2, 3)];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![
+            Value::Array(vec![Value::Int(1), Value::Array(vec![])]),
+            Value::Array(vec![Value::Int(1), Value::from(vec![2, 3])]),
+        ])
+    );
+}
+
+#[test]
+fn calling_with_the_wrong_number_of_arguments_is_an_error_at_the_call() {
+    for (code, expected) in [
+        (
+            "f = fn(a, b) -> a; return f(1);",
+            "expects 2 arguments, but got 1.",
+        ),
+        (
+            "f = fn(a) -> a; return f(1, 2);",
+            "expects 1 argument, but got 2.",
+        ),
+        (
+            "f = fn(a, b = 1) -> a; return f();",
+            "expects 1 to 2 arguments, but got 0.",
+        ),
+        (
+            "f = fn(a, b = 1) -> a; return f(1, 2, 3);",
+            "expects 1 to 2 arguments, but got 3.",
+        ),
+        (
+            "f = fn(a, ...rest) -> a; return f();",
+            "expects at least 1 argument, but got 0.",
+        ),
+    ] {
+        let error = run(code).unwrap_err();
+        assert!(format!("{error:?}").contains(expected), "{error:?}");
+        assert_eq!(error.span().start.col, code.find("f(").unwrap(), "{code}");
+    }
+}
+
+#[test]
+fn spread_arguments_expand_an_array() {
+    let value = run("
+        f = fn(a, b, c) -> [a, b, c];
+        rest = [2, 3];
+        return [f(...[1, 2, 3]), f(1, ...rest), f(...[1], 2, ...[3])];
+    ");
+    let expected = Value::from(vec![1, 2, 3]);
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![expected.clone(), expected.clone(), expected])
+    );
+    assert!(error("f = fn(a) -> a; return f(...1);").contains("Expected array"));
+}
+
+#[test]
+fn named_arguments_bind_by_parameter_name() {
+    let value = run("
+        describe = fn(name, count = 1, unit = \"item\") -> name .. \" \" .. str(count) .. \" \" .. unit;
+        return [
+            describe(\"pears\", unit = \"crate\"),
+            describe(unit = \"box\", name = \"plums\", count = 2),
+        ];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::from(vec!["pears 1 crate", "plums 2 box"])
+    );
+}
+
+#[test]
+fn named_arguments_reach_native_functions() {
+    let value = run("
+        iter = import(\"std/iter\");
+        return iter.collect(iter.range(end = 4, start = 2));
+    ");
+    assert_eq!(value.unwrap(), Value::from(vec![2, 3]));
+}
+
+#[test]
+fn bad_named_arguments_are_errors_at_the_name() {
+    for (code, expected) in [
+        (
+            "f = fn(a) -> a; return f(b = 1);",
+            "This function has no parameter named 'b'.",
+        ),
+        (
+            "f = fn(a) -> a; return f(1, a = 2);",
+            "The argument 'a' was given more than once.",
+        ),
+        (
+            "iter = import(\"std/iter\"); return iter.range(1, start = 2);",
+            "The argument 'start' was given more than once.",
+        ),
+    ] {
+        let error = run(code).unwrap_err();
+        assert!(format!("{error:?}").contains(expected), "{error:?}");
+        let name = expected.split('\'').nth(1).unwrap();
+        let at = code.rfind(&format!("{name} = ")).unwrap();
+        assert_eq!(error.span().start.col, at, "{code}");
+    }
+    assert!(error("f = fn(a, b) -> a; return f(b = 1);").contains("Missing argument 'a'."));
+}
+
+#[test]
+fn optional_chaining_yields_null_for_missing_properties() {
+    let value = run("
+        config = #{output: #{dir: \"build\"}, empty: null};
+        return [
+            config?.output?.dir,
+            config?.output?.format,
+            config?.missing?.deep,
+            config?.empty?.deep,
+            config?.(\"out\" .. \"put\")?.dir,
+        ];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![
+            Value::from("build"),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::from("build"),
+        ])
+    );
+    assert!(error("config = #{}; return config.missing;").contains("No property"));
+}
+
+#[test]
+fn optional_chaining_skips_the_rest_of_the_chain() {
+    let value = run("
+        calls = 0;
+        touch = fn() -> {
+            calls += 1;
+            return #{};
+        };
+        settings = null;
+        a = settings?.display.theme;
+        b = settings?.make(touch()).describe();
+        return [a, b, calls];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![Value::Null, Value::Null, Value::Int(0)])
+    );
+}
+
+#[test]
+fn optional_chains_continue_through_calls() {
+    let value = run("m = #{f: fn() -> #{d: 4}}; return m?.f().d;");
+    assert_eq!(value.unwrap(), Value::Int(4));
+    // Only a `?.` skips the rest; a call that returns null doesn't.
+    assert!(error("m = #{f: fn() -> null}; return m.f().d;").contains("Expected map"));
+}
+
+#[test]
+fn optional_chaining_on_a_value_that_isnt_a_map_is_an_error() {
+    assert!(error("return 1?.x;").contains("Expected map, but instead found int"));
+}
+
+#[test]
+fn coalesce_only_replaces_null() {
+    let value = run("return [null ?? 1, false ?? 1, 0 ?? 1, \"a\" ?? 1, null ?? null ?? 2];");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![
+            Value::Int(1),
+            Value::Boolean(false),
+            Value::Int(0),
+            Value::from("a"),
+            Value::Int(2),
+        ])
+    );
+}
+
+#[test]
+fn coalesce_skips_the_right_side_when_the_left_isnt_null() {
+    let value = run("
+        calls = 0;
+        count = fn() -> {
+            calls += 1;
+            return calls;
+        };
+        a = \"present\" ?? count();
+        b = null ?? count();
+        return [a, b, calls];
+    ");
+    assert_eq!(
+        value.unwrap(),
+        Value::Array(vec![Value::from("present"), Value::Int(1), Value::Int(1)])
+    );
+}
+
+#[test]
+fn object_literals_hold_only_the_listed_keys() {
+    let value = run("
+        name = \"pack\";
+        return #{name, \"min-format\": 48, version: \"1.0\"};
+    ");
+    assert_eq!(
+        value.unwrap().to_string(),
+        "{ min-format: 48, name: \"pack\", version: \"1.0\" }"
+    );
+    assert_eq!(run("return #{};").unwrap().to_string(), "{}");
+}
+
+#[test]
+fn object_literals_can_have_computed_keys() {
+    let value = run("
+        key = \"dep\";
+        return #{[key .. \"s\"]: 1, [\"a\" .. \"b\"]: 2};
+    ");
+    assert_eq!(value.unwrap().to_string(), "{ ab: 2, deps: 1 }");
+
+    let code = "return #{[1]: 2};";
+    let error = run(code).unwrap_err();
+    assert!(format!("{error:?}").contains("Expected string, but instead found int"));
+    assert_eq!(error.span().start.col, code.find('1').unwrap());
+}
+
+#[test]
+fn spread_copies_entries_and_later_keys_win() {
+    let value = run("
+        defaults = #{a: 1, b: 2};
+        merged = #{a: 0, ...defaults, b: 3};
+        merged.a = 10;
+        return [merged, defaults];
+    ");
+    assert_eq!(
+        value.unwrap().to_string(),
+        "[{ a: 10, b: 3 }, { a: 1, b: 2 }]"
+    );
+    assert!(error("return #{...1};").contains("Expected map, but instead found int"));
+}
@@ -13,6 +13,8 @@ pub struct TokenStream<'a> {
 
     done: bool,
     pos: Pos,
+    /// The text of each `//` comment seen so far, without the slashes.
+    comments: Vec<Chunk<&'a str>>,
 }
 
 impl<'a> From<&'a str> for TokenStream<'a> {
@@ -24,11 +26,16 @@ impl<'a> From<&'a str> for TokenStream<'a> {
 
             done: source.is_empty(),
             pos: Pos::default(),
+            comments: Vec::new(),
         }
     }
 }
 
 impl<'a> TokenStream<'a> {
+    pub fn comments(&self) -> &[Chunk<&'a str>] {
+        &self.comments
+    }
+
     fn next_char(&mut self) -> Option<(usize, char)> {
         let mut c: (usize, char);
         loop {
@@ -95,11 +102,21 @@ impl<'a> TokenStream<'a> {
 
         match char.1 {
             '/' if matches!(self.chars.peek(), Some((_, '/'))) => {
-                loop {
-                    if self.chars.next()?.1 == '\n' {
-                        break;
+                let text_start = char.0 + 2;
+                let text_end = loop {
+                    match self.chars.next() {
+                        Some((i, '\n')) => {
+                            self.pos.col = 0;
+                            self.pos.ln += 1;
+                            break i;
+                        }
+                        Some(_) => (),
+                        None => break self.source.len(),
                     }
-                }
+                };
+                let text = self.source.get(text_start..text_end).unwrap_or_default();
+                self.comments
+                    .push(Chunk::new(text, Span { start, end: start }));
                 self.force_next()
             }
             '/' if matches!(self.chars.peek(), Some((_, '/'))) => {
@@ -245,52 +262,73 @@ impl<'a> TokenStream<'a> {
             }
 
             '<' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(LtEquals, self.pos)
             }
             '>' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(GtEquals, self.pos)
             }
             '=' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(DoubleEquals, self.pos)
             }
             '!' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(NotEquals, self.pos)
             }
             '+' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(PlusEquals, self.pos)
             }
             '-' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(MinusEquals, self.pos)
             }
             '*' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(AsteriskEquals, self.pos)
             }
             '/' if matches!(self.chars.peek(), Some((_, '='))) => {
-                self.next();
+                self.next_char();
                 double_operator!(SlashEquals, self.pos)
             }
             '&' if matches!(self.chars.peek(), Some((_, '&'))) => {
-                self.next();
+                self.next_char();
                 double_operator!(DoubleAmpersand, self.pos)
             }
             '|' if matches!(self.chars.peek(), Some((_, '|'))) => {
-                self.next();
+                self.next_char();
                 double_operator!(DoublePipe, self.pos)
             }
+            '?' if matches!(self.chars.peek(), Some((_, '.'))) => {
+                self.next_char();
+                double_operator!(QuestionPeriod, self.pos)
+            }
+            '?' if matches!(self.chars.peek(), Some((_, '?'))) => {
+                self.next_char();
+                double_operator!(DoubleQuestion, self.pos)
+            }
             '-' if matches!(self.chars.peek(), Some((_, '>'))) => {
-                self.next();
+                self.next_char();
                 double_operator!(Arrow, self.pos)
             }
             '.' if matches!(self.chars.peek(), Some((_, '.'))) => {
-                self.next();
-                double_operator!(DoublePeriod, self.pos)
+                self.chars.next();
+                self.pos.col += 1;
+                if matches!(self.chars.peek(), Some((_, '.'))) {
+                    self.chars.next();
+                    self.pos.col += 1;
+                    Some(Ok(Chunk::new(
+                        Token::Ellipsis,
+                        Span {
+                            start,
+                            end: self.pos,
+                        },
+                    )))
+                } else {
+                    double_operator!(DoublePeriod, self.pos)
+                }
             }
 
             ';' => operator!(Semicolon, self.pos),
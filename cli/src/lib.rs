@@ -0,0 +1,26 @@
+//! The foliose interpreter. Scripts are run by an [`Engine`](engine::Engine), and each
+//! engine has its own modules, limits and cycle collector, so hosts can run several
+//! isolated interpreters side by side.
+
+use compiler::{CompileChunk, CompilerScope};
+use lib::{error::Result, instruction::Instruction, span::Chunk};
+use parser::Parse;
+
+pub mod checker;
+mod compiler;
+pub mod engine;
+mod interpreter;
+mod lexer;
+pub mod lint;
+mod parser;
+mod std_lib;
+
+pub fn compile(code: &str) -> Result<Chunk<Vec<Chunk<Instruction<'_>>>>> {
+    let mut tokens = lexer::TokenStream::from(code);
+
+    let program = parser::block::Block::parse(&mut tokens)?;
+    //dbg!(&program);
+
+    let mut prelude_scope = CompilerScope::from_prelude();
+    program.compile(&mut prelude_scope)
+}
@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet};
+
+use lib::{
+    error::Error,
+    instruction::{Argument, Instruction, ObjectEntry, Reporter},
+    span::{Chunk, Pos, Span},
+};
+
+use crate::lexer::TokenStream;
+
+pub const UNUSED_VARIABLE: &str = "unused_variable";
+pub const UNUSED_PARAMETER: &str = "unused_parameter";
+pub const UNUSED_ASSIGNMENT: &str = "unused_assignment";
+pub const UNREACHABLE_CODE: &str = "unreachable_code";
+pub const SHADOWED_VARIABLE: &str = "shadowed_variable";
+pub const CONSTANT_CONDITION: &str = "constant_condition";
+pub const IMPOSSIBLE_COMPARISON: &str = "impossible_comparison";
+pub const UNKNOWN_LINT: &str = "unknown_lint";
+
+const LINTS: [&str; 8] = [
+    UNUSED_VARIABLE,
+    UNUSED_PARAMETER,
+    UNUSED_ASSIGNMENT,
+    UNREACHABLE_CODE,
+    SHADOWED_VARIABLE,
+    CONSTANT_CONDITION,
+    IMPOSSIBLE_COMPARISON,
+    UNKNOWN_LINT,
+];
+
+/// A warning found by [`lint`]. Lints can be allowed for one line with a
+/// `// allow(name)` comment at the end of the line or on the line before, and for a
+/// whole file with `// allow-file(name)`. Allowing a name that isn't a lint is itself
+/// an `unknown_lint` warning.
+pub struct Lint {
+    pub name: &'static str,
+    pub warning: Error,
+}
+
+impl Lint {
+    pub fn display(&self, src: &str) {
+        self.warning
+            .display_as(&format!("warning[{}]", self.name), src);
+    }
+}
+
+pub fn lint(source: &str, program: &[Chunk<Instruction>]) -> Vec<Lint> {
+    let mut linter = Linter {
+        frames: Vec::new(),
+        next_id: 0,
+        lints: Vec::new(),
+    };
+    // The prelude, which is never linted.
+    linter.frames.push(Frame::new(0, false));
+    linter.next_id = 1;
+    // The top level scope is what importing the file gives, but it is still linted, as
+    // most files are run rather than imported. Modules can allow `unused_variable`.
+    linter.block(HashMap::new(), program, true);
+
+    let allowed = Allowed::parse(source, &mut linter);
+    linter
+        .lints
+        .into_iter()
+        .filter(|lint| !allowed.allows(lint))
+        .collect()
+}
+
+struct Allowed<'a> {
+    file: Vec<&'a str>,
+    lines: HashMap<usize, Vec<&'a str>>,
+}
+
+impl<'a> Allowed<'a> {
+    /// Reads the allow comments in `source`, warning about names that aren't lints.
+    fn parse(source: &'a str, linter: &mut Linter) -> Self {
+        fn names<'a>(comment: &'a str, prefix: &str) -> Option<impl Iterator<Item = &'a str>> {
+            let names = comment.strip_prefix(prefix)?.strip_suffix(')')?;
+            Some(names.split(',').map(str::trim))
+        }
+        // The span of `name`, which is part of the text of `comment`.
+        fn span_of(comment: &Chunk<&str>, name: &str) -> Span {
+            let offset = name.as_ptr() as usize - comment.data.as_ptr() as usize;
+            // The text starts after the slashes.
+            let col = comment.span.start.col + 2 + comment.data[..offset].chars().count();
+            let start = Pos {
+                col,
+                ln: comment.span.start.ln,
+            };
+            let end = Pos {
+                col: col + name.chars().count(),
+                ..start
+            };
+            Span { start, end }
+        }
+
+        // Comments come from the lexer, so `//` inside a string isn't taken for one.
+        let mut tokens = TokenStream::from(source);
+        let mut code_lines = HashSet::new();
+        while let Some(Ok(token)) = tokens.next() {
+            code_lines.insert(token.span.start.ln);
+        }
+
+        let mut allowed = Self {
+            file: Vec::new(),
+            lines: HashMap::new(),
+        };
+        for comment in tokens.comments() {
+            let ln = comment.span.start.ln;
+            let text = comment.data.trim();
+            let (names, target) = if let Some(names) = names(text, "allow-file(") {
+                (names.collect::<Vec<_>>(), &mut allowed.file)
+            } else if let Some(names) = names(text, "allow(") {
+                let target = if code_lines.contains(&ln) { ln } else { ln + 1 };
+                (names.collect(), allowed.lines.entry(target).or_default())
+            } else {
+                continue;
+            };
+            for name in names {
+                if !LINTS.contains(&name) {
+                    linter.warn(
+                        UNKNOWN_LINT,
+                        format!("There is no lint named '{name}'."),
+                        span_of(comment, name),
+                    );
+                }
+                target.push(name);
+            }
+        }
+        allowed
+    }
+
+    fn allows(&self, lint: &Lint) -> bool {
+        self.file.contains(&lint.name)
+            || self
+                .lines
+                .get(&lint.warning.span().start.ln)
+                .is_some_and(|names| names.contains(&lint.name))
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum Kind {
+    Local,
+    Parameter,
+}
+
+struct Variable {
+    kind: Kind,
+    span: Span,
+    read: bool,
+    /// The last assignment that hasn't been read yet, and the block it happened in.
+    pending: Option<(Span, usize)>,
+}
+
+struct Frame {
+    id: usize,
+    variables: HashMap<String, Variable>,
+    /// Whether the scope is used as a value after it ends, making its variables fields.
+    escapes: bool,
+}
+
+impl Frame {
+    fn new(id: usize, escapes: bool) -> Self {
+        Self {
+            id,
+            variables: HashMap::new(),
+            escapes,
+        }
+    }
+}
+
+/// Walks the IR with a frame for each scope, in the same way as the checker.
+struct Linter {
+    frames: Vec<Frame>,
+    next_id: usize,
+    lints: Vec<Lint>,
+}
+
+impl Linter {
+    fn warn(&mut self, name: &'static str, message: impl Into<String>, span: Span) {
+        self.lints.push(Lint {
+            name,
+            warning: Error::new(message, span),
+        });
+    }
+
+    fn push(&mut self, variables: HashMap<String, Variable>) {
+        let mut frame = Frame::new(self.next_id, false);
+        frame.variables = variables;
+        self.next_id += 1;
+        self.frames.push(frame);
+    }
+
+    fn pop(&mut self) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        if frame.escapes {
+            return;
+        }
+        let mut unused = frame
+            .variables
+            .into_iter()
+            .filter(|(name, var)| !var.read && !name.starts_with('_'))
+            .collect::<Vec<_>>();
+        unused.sort_by_key(|(_, var)| (var.span.start.ln, var.span.start.col));
+        for (name, var) in unused {
+            match var.kind {
+                Kind::Local => self.warn(
+                    UNUSED_VARIABLE,
+                    format!("The variable '{name}' is never used."),
+                    var.span,
+                ),
+                Kind::Parameter => self.warn(
+                    UNUSED_PARAMETER,
+                    format!("The parameter '{name}' is never used."),
+                    var.span,
+                ),
+            }
+        }
+    }
+
+    fn frame(&mut self, up: usize) -> Option<&mut Frame> {
+        let index = self.frames.len().checked_sub(up + 1)?;
+        self.frames.get_mut(index)
+    }
+
+    /// A new variable for a parameter or loop, which can hide a variable of the same name.
+    fn declare(&mut self, name: &Chunk<&str>, kind: Kind) -> (String, Variable) {
+        // The first frame is the prelude.
+        if self.frames[1..]
+            .iter()
+            .any(|frame| frame.variables.contains_key(name.data))
+        {
+            let what = match kind {
+                Kind::Local => "loop variable",
+                Kind::Parameter => "parameter",
+            };
+            self.warn(
+                SHADOWED_VARIABLE,
+                format!(
+                    "The {what} '{}' hides a variable from an outer scope.",
+                    name.data
+                ),
+                name.span,
+            );
+        }
+        (
+            name.data.to_string(),
+            Variable {
+                kind,
+                span: name.span,
+                read: false,
+                pending: None,
+            },
+        )
+    }
+
+    fn read(&mut self, up: usize, name: &str) {
+        if let Some(var) = self
+            .frame(up)
+            .and_then(|frame| frame.variables.get_mut(name))
+        {
+            var.read = true;
+            var.pending = None;
+        }
+    }
+
+    fn write(&mut self, up: usize, name: &Chunk<&str>, span: Span) {
+        let block = self.frames.last().map_or(0, |frame| frame.id);
+        let Some(frame) = self.frame(up) else {
+            return;
+        };
+        let var = frame
+            .variables
+            .entry(name.data.to_string())
+            .or_insert(Variable {
+                kind: Kind::Local,
+                span: name.span,
+                read: false,
+                pending: None,
+            });
+        // Assignments in other blocks might not run, so only a following assignment in
+        // the same block is sure to overwrite the value.
+        let overwritten = var.pending.filter(|(_, id)| *id == block);
+        var.pending = Some((span, block));
+        if let Some((span, _)) = overwritten {
+            self.warn(
+                UNUSED_ASSIGNMENT,
+                format!("The value assigned to '{}' is never read.", name.data),
+                span,
+            );
+        }
+    }
+
+    /// Calls can run any closure, which might read any variable.
+    fn forget_pending(&mut self) {
+        for frame in &mut self.frames {
+            for var in frame.variables.values_mut() {
+                var.pending = None;
+            }
+        }
+    }
+
+    /// `discarded` is whether the value of the block is thrown away. Blocks that are
+    /// used as values without returning evaluate to their scope.
+    fn block(
+        &mut self,
+        variables: HashMap<String, Variable>,
+        body: &[Chunk<Instruction>],
+        discarded: bool,
+    ) {
+        self.push(variables);
+        let mut returned = false;
+        let mut warned = false;
+        for instruction in body {
+            if returned && !warned {
+                self.warn(
+                    UNREACHABLE_CODE,
+                    "This code comes after a return, so it will never run.",
+                    instruction.span,
+                );
+                warned = true;
+            }
+            match &instruction.data {
+                Instruction::Set {
+                    map, name, value, ..
+                } => {
+                    self.reporter(&value.data, value.span, false);
+                    match map.data {
+                        Reporter::Parent(up) => self.write(up, name, instruction.span),
+                        _ => self.reporter(&map.data, map.span, false),
+                    }
+                }
+                Instruction::While { condition, body } => {
+                    self.reporter(&condition.data, condition.span, false);
+                    self.block(HashMap::new(), &body.data, true);
+                }
+                Instruction::For { name, iter, body } => {
+                    self.reporter(&iter.data, iter.span, false);
+                    let variable = self.declare(name, Kind::Local);
+                    self.block(HashMap::from([variable]), &body.data, true);
+                }
+                Instruction::Return(value) => {
+                    self.reporter(&value.data, value.span, false);
+                    returned = true;
+                }
+                Instruction::Void(reporter) => self.reporter(reporter, instruction.span, true),
+            }
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.escapes = !discarded && !returned;
+        }
+        self.pop();
+    }
+
+    fn reporter(&mut self, reporter: &Reporter, span: Span, discarded: bool) {
+        match reporter {
+            Reporter::Parent(_)
+            | Reporter::Null
+            | Reporter::ConstStr(_)
+            | Reporter::ConstInt(_)
+            | Reporter::ConstFloat(_)
+            | Reporter::ConstBool(_)
+            | Reporter::Import(_)
+            | Reporter::RecordType { .. } => (),
+
+            Reporter::Block(body) => self.block(HashMap::new(), body, discarded),
+            Reporter::Array(items) => {
+                for item in &items.data {
+                    self.reporter(&item.data, item.span, false);
+                }
+            }
+            Reporter::Object(entries) => {
+                for entry in entries {
+                    match &entry.data {
+                        ObjectEntry::Field(_, value) => {
+                            self.reporter(&value.data, value.span, false)
+                        }
+                        ObjectEntry::Computed(key, value) => {
+                            self.reporter(&key.data, key.span, false);
+                            self.reporter(&value.data, value.span, false);
+                        }
+                        ObjectEntry::Spread(value) => self.reporter(value, entry.span, false),
+                    }
+                }
+            }
+            Reporter::Function {
+                parameters, body, ..
+            } => {
+                self.push(HashMap::new());
+                for parameter in parameters {
+                    if let Some(default) = &parameter.default {
+                        self.reporter(&default.data, default.span, false);
+                    }
+                    let (name, variable) = self.declare(&parameter.name, Kind::Parameter);
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.variables.insert(name, variable);
+                    }
+                }
+                self.reporter(&body.data, body.span, false);
+                self.pop();
+            }
+
+            Reporter::Get { map, name, .. } => match map.data.as_ref() {
+                Reporter::Parent(up) => self.read(*up, name.data),
+                map_data => self.reporter(map_data, map.span, false),
+            },
+            Reporter::DynGet { map, attr, .. } => {
+                self.reporter(&map.data, map.span, false);
+                self.reporter(&attr.data, attr.span, false);
+            }
+            Reporter::Call(function, args) => {
+                self.reporter(&function.data, function.span, false);
+                for arg in args {
+                    match &arg.data {
+                        Argument::Positional(value) | Argument::Spread(value) => {
+                            self.reporter(value, arg.span, false)
+                        }
+                        Argument::Named(_, value) => self.reporter(&value.data, value.span, false),
+                    }
+                }
+                self.forget_pending();
+            }
+            Reporter::If { blocks, else_block } => {
+                for (condition, body) in blocks {
+                    if let Some(value) = constant(&condition.data) {
+                        self.warn(
+                            CONSTANT_CONDITION,
+                            format!("This condition is always {value}."),
+                            condition.span,
+                        );
+                    }
+                    self.reporter(&condition.data, condition.span, false);
+                    self.reporter(&body.data, body.span, discarded);
+                }
+                if let Some(body) = else_block {
+                    self.reporter(&body.data, body.span, discarded);
+                }
+            }
+
+            Reporter::Equality { a, b } => {
+                if let (Some(a_ty), Some(b_ty)) = (literal_type(&a.data), literal_type(&b.data))
+                    && a_ty != b_ty
+                {
+                    self.warn(
+                        IMPOSSIBLE_COMPARISON,
+                        format!(
+                            "{} is never equal to {}.",
+                            article(a_ty, "A"),
+                            article(b_ty, "a")
+                        ),
+                        span,
+                    );
+                }
+                self.reporter(&a.data, a.span, false);
+                self.reporter(&b.data, b.span, false);
+            }
+            Reporter::Add { a, b }
+            | Reporter::Subtract { a, b }
+            | Reporter::Multiply { a, b }
+            | Reporter::Divide { a, b }
+            | Reporter::Exponent { a, b }
+            | Reporter::Concat { a, b }
+            | Reporter::And { a, b }
+            | Reporter::Or { a, b }
+            | Reporter::Coalesce { a, b }
+            | Reporter::Inequality { a, b, .. } => {
+                self.reporter(&a.data, a.span, false);
+                self.reporter(&b.data, b.span, false);
+            }
+            Reporter::Not(value) | Reporter::Negative(value) => {
+                self.reporter(&value.data, value.span, false)
+            }
+        }
+    }
+}
+
+/// The type of a value that can be known without running the program.
+fn literal_type(reporter: &Reporter) -> Option<&'static str> {
+    Some(match reporter {
+        Reporter::Null => "null",
+        Reporter::ConstStr(_) | Reporter::Concat { .. } => "string",
+        Reporter::ConstInt(_) => "int",
+        Reporter::ConstFloat(_) => "float",
+        Reporter::ConstBool(_)
+        | Reporter::Not(_)
+        | Reporter::And { .. }
+        | Reporter::Or { .. }
+        | Reporter::Equality { .. }
+        | Reporter::Inequality { .. } => "bool",
+        Reporter::Array(_) => "array",
+        Reporter::Object(_) => "map",
+        Reporter::Function { .. } | Reporter::RecordType { .. } => "function",
+        _ => return None,
+    })
+}
+
+fn article(ty: &str, a: &str) -> String {
+    if ty.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        format!("{a}n {ty}")
+    } else {
+        format!("{a} {ty}")
+    }
+}
+
+/// The value of a condition that doesn't depend on anything.
+fn constant(reporter: &Reporter) -> Option<bool> {
+    match reporter {
+        Reporter::ConstBool(value) => Some(*value),
+        Reporter::Not(value) => constant(&value.data).map(|value| !value),
+        Reporter::And { a, b } => match (constant(&a.data), constant(&b.data)) {
+            (Some(false), _) => Some(false),
+            (Some(true), b) => b,
+            _ => None,
+        },
+        Reporter::Or { a, b } => match (constant(&a.data), constant(&b.data)) {
+            (Some(true), _) => Some(true),
+            (Some(false), b) => b,
+            _ => None,
+        },
+        Reporter::Equality { a, b } => match (&a.data.as_ref(), &b.data.as_ref()) {
+            (Reporter::Null, Reporter::Null) => Some(true),
+            (Reporter::ConstStr(a), Reporter::ConstStr(b)) => Some(a == b),
+            (Reporter::ConstInt(a), Reporter::ConstInt(b)) => Some(a == b),
+            (Reporter::ConstFloat(a), Reporter::ConstFloat(b)) => Some(a == b),
+            (Reporter::ConstBool(a), Reporter::ConstBool(b)) => Some(a == b),
+            // Comparisons of different types are reported as impossible comparisons.
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,105 @@
+use super::*;
+use crate::compile;
+
+/// The names of the lints found in `code`, in the order they were found.
+fn lints(code: &str) -> Vec<&'static str> {
+    let program = compile(code).unwrap();
+    lint(code, &program.data)
+        .into_iter()
+        .map(|lint| lint.name)
+        .collect()
+}
+
+#[test]
+fn unused_variables() {
+    assert_eq!(
+        lints("f = fn() -> { x = 1; return 2; }; f();"),
+        [UNUSED_VARIABLE]
+    );
+    assert!(lints("f = fn() -> { _x = 1; return 2; }; f();").is_empty());
+}
+
+#[test]
+fn unused_parameters() {
+    assert_eq!(lints("f = fn(a) -> 1; f(2);"), [UNUSED_PARAMETER]);
+    assert!(lints("f = fn(a) -> a; f(2);").is_empty());
+}
+
+#[test]
+fn unused_assignments() {
+    assert_eq!(
+        lints("f = fn() -> { x = 1; x = 2; return x; }; f();"),
+        [UNUSED_ASSIGNMENT]
+    );
+}
+
+#[test]
+fn unreachable_code() {
+    assert_eq!(
+        lints("f = fn() -> { return 1; println(2); }; f();"),
+        [UNREACHABLE_CODE]
+    );
+}
+
+#[test]
+fn shadowed_variables() {
+    assert_eq!(lints("a = 1; f = fn(a) -> a; f(a);"), [SHADOWED_VARIABLE]);
+}
+
+#[test]
+fn constant_conditions() {
+    assert_eq!(
+        lints("f = fn(n) -> { if true -> println(n); }; f(1);"),
+        [CONSTANT_CONDITION]
+    );
+}
+
+#[test]
+fn impossible_comparisons() {
+    assert_eq!(
+        lints("f = fn(n) -> n == 1; println(f(1) == \"one\", 1 == \"one\");"),
+        [IMPOSSIBLE_COMPARISON]
+    );
+}
+
+#[test]
+fn a_line_can_be_allowed_at_its_end_or_on_the_line_before() {
+    let code = "
+        f = fn(a) -> 1; // allow(unused_parameter)
+        // allow(unused_parameter)
+        g = fn(b) -> 1;
+        h = fn(c) -> 1;
+        println(f(1), g(1), h(1));
+    ";
+    let found = lint(code, &compile(code).unwrap().data);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].name, UNUSED_PARAMETER);
+    assert_eq!(found[0].warning.span().start.ln, 4);
+}
+
+#[test]
+fn a_file_can_allow_lints() {
+    let code = "
+        // allow-file(unused_parameter, unreachable_code)
+        f = fn(a) -> { return 1; println(2); };
+        f(1);
+    ";
+    assert!(lints(code).is_empty());
+}
+
+#[test]
+fn allowing_an_unknown_lint_is_a_warning() {
+    for (code, name) in [
+        ("println(1); // allow(unused_varaible)", "unused_varaible"),
+        (
+            "// allow-file(unused_variable, unsued_parameter)",
+            "unsued_parameter",
+        ),
+    ] {
+        let found = lint(code, &compile(code).unwrap().data);
+        assert_eq!(found.len(), 1, "{code}");
+        assert_eq!(found[0].name, UNKNOWN_LINT);
+        let span = found[0].warning.span();
+        assert_eq!(&code[span.start.col..span.end.col], name);
+    }
+}
@@ -1,38 +1,138 @@
-use interpreter::{Scope, run_block};
-use lib::{error::Result, value::Value};
-use std::{cell::RefCell, env::args, fs, rc::Rc};
-use std_lib::{PRELUDE, init_registry};
+use foliose::{
+    checker::Checker,
+    compile,
+    engine::{Capabilities, Engine, Limits},
+    lint,
+};
+use lib::span::Span;
+use std::{
+    env::{Args, args},
+    fs, process,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
 
-use compiler::{CompileChunk, CompilerScope};
-use parser::Parse;
+/// Native stack reserved for each nested script call. Calls are evaluated recursively,
+/// so the interpreter thread needs room for the deepest call chain allowed.
+const STACK_PER_CALL: usize = 256 * 1024;
+/// The largest stack the interpreter thread is given, which bounds `--max-depth`.
+const MAX_THREAD_STACK: usize = 1024 * 1024 * 1024;
+/// Stack kept free for the CLI and native functions, beyond what scripts may use.
+const STACK_MARGIN: usize = 1024 * 1024;
+/// The CLI sizes its own thread for the depth limit, so it allows deeper recursion than
+/// engines do by default.
+const CLI_MAX_DEPTH: usize = 1000;
 
-mod compiler;
-mod interpreter;
-mod lexer;
-mod parser;
-mod std_lib;
+fn main() {
+    let mut args = args();
+    args.next();
+    let mut path = args.next().unwrap();
+    let mut limits = Limits {
+        max_depth: CLI_MAX_DEPTH,
+        ..Limits::default()
+    };
+    let mut capabilities = Capabilities::default();
+    while let Some(option) = path.strip_prefix("--") {
+        match option {
+            "max-depth" => limits.max_depth = number(&mut args, option),
+            "max-steps" => limits.max_steps = Some(number(&mut args, option)),
+            "max-collection-size" => limits.max_collection_size = Some(number(&mut args, option)),
+            "timeout" => limits.timeout = Some(Duration::from_secs_f64(number(&mut args, option))),
+            // Scripts can only import registered modules that don't reach outside, unless
+            // allowed to with `--allow`.
+            "sandbox" => capabilities = Capabilities::none(),
+            "allow" => capabilities.allow(
+                args.next()
+                    .expect("--allow expects a capability")
+                    .parse()
+                    .unwrap_or_else(|e| panic!("{e}")),
+            ),
+            _ => panic!("Unknown option '--{option}'."),
+        }
+        path = args.next().unwrap();
+    }
+    let target = args.next();
+    let Some(stack_size) = (limits.max_depth.checked_add(16))
+        .and_then(|calls| calls.checked_mul(STACK_PER_CALL))
+        .filter(|size| *size <= MAX_THREAD_STACK)
+    else {
+        eprintln!(
+            "--max-depth {} is too deep, the most allowed is {}.",
+            limits.max_depth,
+            MAX_THREAD_STACK / STACK_PER_CALL - 16
+        );
+        process::exit(1);
+    };
+    limits.max_stack = stack_size - STACK_MARGIN;
 
-pub fn run(code: &str) -> Result<Value> {
-    let mut tokens = lexer::TokenStream::from(code);
+    let interpreter = thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || {
+            match path.as_str() {
+                "check" => return check(&target.unwrap()),
+                "lint" => return lint(&target.unwrap()),
+                _ => (),
+            }
+            let engine = Engine::new();
+            engine.set_limits(limits);
+            engine.set_capabilities(capabilities);
+            let file = fs::read_to_string(path).unwrap();
+            let result = engine.run(&file);
+            for warning in engine.take_warnings() {
+                warning.display_as("warning", &file);
+            }
+            if let Err(e) = result {
+                e.display(&file);
+            }
+        });
+    match interpreter {
+        Ok(interpreter) => interpreter.join().unwrap(),
+        Err(e) => {
+            eprintln!("Couldn't start the interpreter with {stack_size} bytes of stack: {e}");
+            process::exit(1);
+        }
+    }
+}
 
-    let program = parser::block::Block::parse(&mut tokens)?;
-    //dbg!(&program);
+fn number<T: FromStr>(args: &mut Args, option: &str) -> T {
+    args.next()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| panic!("--{option} expects a number"))
+}
 
-    let mut prelude_scope = CompilerScope::from_prelude();
-    let instruction_set = program.compile(&mut prelude_scope)?;
-    //dbg!(&instruction_set);
+/// Type checks a module and its imports without running them.
+fn check(path: &str) {
+    let mut checker = Checker::new();
+    if let Err(e) = checker.check_file(path, Span::default()) {
+        e.display("");
+        process::exit(1);
+    }
 
-    let scope = Rc::new(RefCell::new(Scope::new(Some(PRELUDE.with(Clone::clone)))));
-    run_block(&scope, instruction_set.data)
+    let mut failed = false;
+    for report in checker.reports.iter().filter(|r| !r.errors.is_empty()) {
+        println!("in {}:", report.path);
+        for error in &report.errors {
+            error.display(&report.source);
+        }
+        failed = true;
+    }
+    if failed {
+        process::exit(1);
+    }
 }
 
-fn main() {
-    init_registry();
-
-    let mut args = args();
-    args.next();
-    let file = fs::read_to_string(args.next().unwrap()).unwrap();
-    if let Err(e) = run(&file) {
-        e.display(&file);
+/// Prints warnings for code that is probably a mistake.
+fn lint(path: &str) {
+    let file = fs::read_to_string(path).unwrap();
+    let program = match compile(&file) {
+        Ok(program) => program,
+        Err(e) => {
+            e.display(&file);
+            process::exit(1);
+        }
+    };
+    for lint in lint::lint(&file, &program.data) {
+        lint.display(&file);
     }
 }
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use lib::{
     error::{Error, Result},
     span::{Chunk, Span},
@@ -16,17 +18,21 @@ pub enum Expression<'a> {
     Float(f64),
     Boolean(bool),
     Array(Vec<Chunk<Self>>),
+    Object(Vec<Chunk<ObjectEntry<'a>>>),
     Function {
-        parameters: Vec<Chunk<&'a str>>,
+        parameters: Vec<Chunk<Parameter<'a>>>,
+        returns: Option<Chunk<&'a str>>,
         body: Chunk<Box<Self>>,
     },
 
     Variable(&'a str),
     GetProp(Chunk<Box<Self>>, Chunk<&'a str>),
     DynProp(Chunk<Box<Self>>, Chunk<Box<Self>>),
+    OptionalProp(Chunk<Box<Self>>, Chunk<&'a str>),
+    OptionalDynProp(Chunk<Box<Self>>, Chunk<Box<Self>>),
     Call {
         value: Chunk<Box<Self>>,
-        args: Vec<Chunk<Self>>,
+        args: Vec<Chunk<Argument<'a>>>,
     },
 
     Block(Block<'a>),
@@ -48,7 +54,29 @@ pub enum Expression<'a> {
     Group(Box<Self>),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug)]
+pub struct Parameter<'a> {
+    pub name: Chunk<&'a str>,
+    pub ty: Option<Chunk<&'a str>>,
+    pub default: Option<Chunk<Expression<'a>>>,
+    pub rest: bool,
+}
+
+#[derive(Debug)]
+pub enum ObjectEntry<'a> {
+    Field(Chunk<&'a str>, Chunk<Expression<'a>>),
+    Computed(Chunk<Expression<'a>>, Chunk<Expression<'a>>),
+    Spread(Expression<'a>),
+}
+
+#[derive(Debug)]
+pub enum Argument<'a> {
+    Positional(Expression<'a>),
+    Spread(Expression<'a>),
+    Named(Chunk<&'a str>, Chunk<Expression<'a>>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Operation {
     Concat,
     Exponent,
@@ -64,6 +92,41 @@ pub enum Operation {
     GtEqual,
     And,
     Or,
+    Coalesce,
+    /// `a |> f(b)` calls `f(a, b)`.
+    Pipe,
+}
+impl Operation {
+    /// How tightly the operation binds. Operations on the same level are grouped from
+    /// left to right, except for exponents.
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Concat => 8,
+            Self::Exponent => 7,
+            Self::Multiply | Self::Divide => 6,
+            Self::Add | Self::Subtract => 5,
+            Self::Equals
+            | Self::NotEquals
+            | Self::Lt
+            | Self::LtEqual
+            | Self::Gt
+            | Self::GtEqual => 4,
+            Self::And => 3,
+            Self::Or => 2,
+            Self::Coalesce => 1,
+            // Below everything, so the whole left side is piped.
+            Self::Pipe => 0,
+        }
+    }
+
+    /// Whether `self` goes inside the right side of `other` when it follows it.
+    fn binds_tighter(self, other: Self) -> bool {
+        match self.precedence().cmp(&other.precedence()) {
+            Ordering::Greater => true,
+            Ordering::Equal => self == Self::Exponent,
+            Ordering::Less => false,
+        }
+    }
 }
 #[derive(Debug, PartialEq, Eq)]
 pub enum UnaryOperation {
@@ -160,6 +223,18 @@ impl<'a> Expression<'a> {
                     },
                 )
             }
+            Some(Token::Hash) => {
+                let start = parse_token(source, Token::Hash)?.start;
+                parse_token(source, Token::BraceOpen)?;
+                let entries = ObjectEntry::parse_group(source, Token::Comma, Token::BraceClose)?.0;
+                Chunk::new(
+                    Self::Object(entries),
+                    Span {
+                        start,
+                        end: *source.pos(),
+                    },
+                )
+            }
 
             Some(Token::KeywordFn) => Self::parse_fn(source)?,
             Some(Token::KeywordIf) => Self::parse_if(source)?,
@@ -231,15 +306,31 @@ impl<'a> Expression<'a> {
             }
         };
 
-        while let Some(Token::Period | Token::BracketOpen | Token::ParenOpen) = source.peek_token()
+        while let Some(
+            Token::Period | Token::QuestionPeriod | Token::BracketOpen | Token::ParenOpen,
+        ) = source.peek_token()
         {
             let start = expr.span.start;
             match source.next().unwrap()?.data {
                 Token::ParenOpen => {
+                    let args = Argument::parse_group(source, Token::Comma, Token::ParenClose)?.0;
+                    let mut named = false;
+                    for arg in &args {
+                        match arg.data {
+                            Argument::Named(..) => named = true,
+                            _ if named => {
+                                return Err(Error::new(
+                                    "Positional arguments can't follow named arguments.",
+                                    arg.span,
+                                ));
+                            }
+                            _ => (),
+                        }
+                    }
                     expr = Chunk::new(
                         Self::Call {
                             value: expr.as_box(),
-                            args: Self::parse_group(source, Token::Comma, Token::ParenClose)?.0,
+                            args,
                         },
                         Span {
                             start,
@@ -247,14 +338,19 @@ impl<'a> Expression<'a> {
                         },
                     );
                 }
-                Token::Period => {
+                token @ (Token::Period | Token::QuestionPeriod) => {
+                    let optional = token == Token::QuestionPeriod;
                     if let Some(Token::ParenOpen) = source.peek_token() {
                         source.next();
                         let property = Self::parse(source)?;
                         parse_token(source, Token::ParenClose)?;
 
                         expr = Chunk::new(
-                            Self::DynProp(expr.as_box(), property.as_box()),
+                            if optional {
+                                Self::OptionalDynProp(expr.as_box(), property.as_box())
+                            } else {
+                                Self::DynProp(expr.as_box(), property.as_box())
+                            },
                             Span {
                                 start,
                                 end: *source.pos(),
@@ -264,7 +360,11 @@ impl<'a> Expression<'a> {
                         let property = <&str>::parse(source)?;
 
                         expr = Chunk::new(
-                            Self::GetProp(expr.as_box(), property),
+                            if optional {
+                                Self::OptionalProp(expr.as_box(), property)
+                            } else {
+                                Self::GetProp(expr.as_box(), property)
+                            },
                             Span {
                                 start,
                                 end: *source.pos(),
@@ -325,17 +425,177 @@ impl<'a> Expression<'a> {
     fn parse_fn(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
         let start = parse_token(source, Token::KeywordFn)?.start;
         parse_token(source, Token::ParenOpen)?;
-        let parameters = <&str>::parse_group(source, Token::Comma, Token::ParenClose)?.0;
+        let parameters = Parameter::parse_group(source, Token::Comma, Token::ParenClose)?.0;
+        let mut has_default = false;
+        for (i, parameter) in parameters.iter().enumerate() {
+            if parameter.data.rest && i + 1 < parameters.len() {
+                return Err(Error::new(
+                    "Only the last parameter can be a rest parameter.",
+                    parameter.span,
+                ));
+            } else if parameter.data.default.is_some() {
+                has_default = true;
+            } else if has_default && !parameter.data.rest {
+                return Err(Error::new(
+                    "A required parameter can't follow a parameter with a default value.",
+                    parameter.span,
+                ));
+            }
+        }
         parse_token(source, Token::Arrow)?;
+        // An arrow can't follow an expression, so `-> name ->` is always a return type.
+        let returns = if matches!(source.peek_token(), Some(Token::Ident(_)))
+            && source.peek_nth_token(2) == Some(&Token::Arrow)
+        {
+            let returns = <&str>::parse(source)?;
+            parse_token(source, Token::Arrow)?;
+            Some(returns)
+        } else {
+            None
+        };
         let body = Self::parse(source)?.as_box();
         let end = body.span.end;
         Ok(Chunk::new(
-            Self::Function { parameters, body },
+            Self::Function {
+                parameters,
+                returns,
+                body,
+            },
             Span { start, end },
         ))
     }
 }
 
+impl<'a> Parse<'a> for Parameter<'a> {
+    fn parse(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        let rest = match source.peek_token() {
+            Some(Token::Ellipsis) => Some(parse_token(source, Token::Ellipsis)?),
+            _ => None,
+        };
+        let name = <&str>::parse(source)?;
+        let ty = if let Some(Token::Colon) = source.peek_token() {
+            source.next();
+            Some(<&str>::parse(source)?)
+        } else {
+            None
+        };
+        let default = if rest.is_none()
+            && let Some(Token::Equals) = source.peek_token()
+        {
+            source.next();
+            Some(Expression::parse(source)?)
+        } else {
+            None
+        };
+        let span = Span {
+            start: rest.map_or(name.span.start, |s| s.start),
+            end: default
+                .as_ref()
+                .map(|d| d.span.end)
+                .or(ty.map(|ty| ty.span.end))
+                .unwrap_or(name.span.end),
+        };
+        Ok(Chunk::new(
+            Self {
+                name,
+                ty,
+                default,
+                rest: rest.is_some(),
+            },
+            span,
+        ))
+    }
+}
+
+impl<'a> Parse<'a> for ObjectEntry<'a> {
+    fn parse(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        let next = source.peek_token().copied();
+        match next {
+            Some(Token::Ellipsis) => {
+                let start = parse_token(source, Token::Ellipsis)?.start;
+                let value = Expression::parse(source)?;
+                let end = value.span.end;
+                Ok(Chunk::new(Self::Spread(value.data), Span { start, end }))
+            }
+            Some(Token::BracketOpen) => {
+                let start = parse_token(source, Token::BracketOpen)?.start;
+                let key = Expression::parse(source)?;
+                parse_token(source, Token::BracketClose)?;
+                parse_token(source, Token::Colon)?;
+                let value = Expression::parse(source)?;
+                let end = value.span.end;
+                Ok(Chunk::new(Self::Computed(key, value), Span { start, end }))
+            }
+            Some(Token::String(key)) => {
+                let key = Chunk::new(key, parse_token(source, Token::String(key))?);
+                parse_token(source, Token::Colon)?;
+                let value = Expression::parse(source)?;
+                let span = Span {
+                    start: key.span.start,
+                    end: value.span.end,
+                };
+                Ok(Chunk::new(Self::Field(key, value), span))
+            }
+            Some(Token::Ident(_)) => {
+                let key = <&str>::parse(source)?;
+                if let Some(Token::Colon) = source.peek_token() {
+                    source.next();
+                    let value = Expression::parse(source)?;
+                    let span = Span {
+                        start: key.span.start,
+                        end: value.span.end,
+                    };
+                    Ok(Chunk::new(Self::Field(key, value), span))
+                } else {
+                    let value = Chunk::new(Expression::Variable(key.data), key.span);
+                    Ok(Chunk::new(Self::Field(key, value), key.span))
+                }
+            }
+            Some(_) => {
+                let Some(Ok(Chunk { span, data: token })) = source.next() else {
+                    unreachable!()
+                };
+                Err(Error::new(
+                    format!("Expected an object entry, but got {}.", token.name()),
+                    span,
+                ))
+            }
+            None => {
+                source.next().transpose()?;
+                Err(Error::new(
+                    "Expected an object entry.",
+                    Span::char(*source.pos()),
+                ))
+            }
+        }
+    }
+}
+
+impl<'a> Parse<'a> for Argument<'a> {
+    fn parse(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        let next = source.peek_token().copied();
+        match (next, source.peek_nth_token(2)) {
+            (Some(Token::Ellipsis), _) => {
+                let start = parse_token(source, Token::Ellipsis)?.start;
+                let value = Expression::parse(source)?;
+                let end = value.span.end;
+                Ok(Chunk::new(Self::Spread(value.data), Span { start, end }))
+            }
+            (Some(Token::Ident(_)), Some(Token::Equals)) => {
+                let name = <&str>::parse(source)?;
+                parse_token(source, Token::Equals)?;
+                let value = Expression::parse(source)?;
+                let span = Span {
+                    start: name.span.start,
+                    end: value.span.end,
+                };
+                Ok(Chunk::new(Self::Named(name, value), span))
+            }
+            _ => Ok(Expression::parse(source)?.map(Self::Positional)),
+        }
+    }
+}
+
 impl<'a> Parse<'a> for Expression<'a> {
     fn parse(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
         let mut expr = Self::parse_single(source)?;
@@ -351,14 +611,16 @@ impl<'a> Parse<'a> for Expression<'a> {
             };
 
             match *lhs.data {
-                Expression::BinaryOp { a, b, op: b_op } if op.data <= b_op.data => Chunk::new(
-                    Expression::BinaryOp {
-                        a,
-                        b: insert(b, op, rhs).as_box(),
-                        op: b_op,
-                    },
-                    span,
-                ),
+                Expression::BinaryOp { a, b, op: b_op } if op.data.binds_tighter(b_op.data) => {
+                    Chunk::new(
+                        Expression::BinaryOp {
+                            a,
+                            b: insert(b, op, rhs).as_box(),
+                            op: b_op,
+                        },
+                        span,
+                    )
+                }
                 _ => Chunk::new(Expression::BinaryOp { a: lhs, b: rhs, op }, span),
             }
         }
@@ -376,10 +638,27 @@ impl<'a> Parse<'a> for Expression<'a> {
             | Token::LtEquals
             | Token::DoubleAmpersand
             | Token::DoublePipe
+            | Token::DoubleQuestion
+            | Token::Pipe
             | Token::DoublePeriod,
         ) = source.peek_token()
         {
-            let op = source.next().unwrap()?.map(|t| match t {
+            let op = source.next().unwrap()?;
+            let op = if op.data == Token::Pipe {
+                // `|>` is lexed as a pipe and a greater than sign, with nothing between.
+                let gt = parse_token(source, Token::Gt)?;
+                if gt.start != op.span.end {
+                    return Err(Error::new("Expected '|>'.", op.span));
+                }
+                let span = Span {
+                    start: op.span.start,
+                    end: gt.end,
+                };
+                Chunk::new(Token::Pipe, span)
+            } else {
+                op
+            };
+            let op = op.map(|t| match t {
                 Token::DoublePeriod => Operation::Concat,
                 Token::Plus => Operation::Add,
                 Token::Minus => Operation::Subtract,
@@ -393,6 +672,8 @@ impl<'a> Parse<'a> for Expression<'a> {
                 Token::LtEquals => Operation::LtEqual,
                 Token::DoubleAmpersand => Operation::And,
                 Token::DoublePipe => Operation::Or,
+                Token::DoubleQuestion => Operation::Coalesce,
+                Token::Pipe => Operation::Pipe,
                 _ => unreachable!(),
             });
 
@@ -14,6 +14,7 @@ pub enum Statement<'a> {
         name: Chunk<Expression<'a>>,
         op: Chunk<AssignOperator>,
         value: Chunk<Expression<'a>>,
+        ty: Option<Chunk<&'a str>>,
     },
     Expr(Expression<'a>),
 
@@ -27,6 +28,19 @@ pub enum Statement<'a> {
         body: Chunk<Block<'a>>,
     },
     Return(Chunk<Expression<'a>>),
+    Type {
+        name: Chunk<&'a str>,
+        fields: Vec<Chunk<Field<'a>>>,
+    },
+    /// `extern a, b;` declares variables that the compiler can't see, so that using
+    /// them isn't an error. They are looked up in the current scope when the program runs.
+    Extern(Vec<Chunk<&'a str>>),
+}
+
+#[derive(Debug)]
+pub struct Field<'a> {
+    pub name: Chunk<&'a str>,
+    pub ty: Chunk<&'a str>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -38,7 +52,67 @@ pub enum AssignOperator {
     Divide,
 }
 
+impl<'a> Parse<'a> for Field<'a> {
+    fn parse(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        let name = <&str>::parse(source)?;
+        parse_token(source, Token::Colon)?;
+        let ty = <&str>::parse(source)?;
+        let span = Span {
+            start: name.span.start,
+            end: ty.span.end,
+        };
+        Ok(Chunk::new(Self { name, ty }, span))
+    }
+}
+
 impl<'a> Statement<'a> {
+    /// `type` is not a keyword, so that `type.of` and friends still work. A type
+    /// declaration is `type` followed by a name and an open curly brace.
+    fn is_type_declaration(source: &mut TokenStream<'a>) -> bool {
+        source.peek_token() == Some(&Token::Ident("type"))
+            && matches!(source.peek_nth_token(2), Some(Token::Ident(_)))
+            && source.peek_nth_token(3) == Some(&Token::BraceOpen)
+    }
+    fn parse_type(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        let start = parse_token(source, Token::Ident("type"))?.start;
+        let name = <&str>::parse(source)?;
+        parse_token(source, Token::BraceOpen)?;
+        let fields = Field::parse_group(source, Token::Comma, Token::BraceClose)?.0;
+        Ok(Chunk::new(
+            Self::Type { name, fields },
+            Span {
+                start,
+                end: *source.pos(),
+            },
+        ))
+    }
+
+    /// Like `type`, `extern` is only a keyword when it's followed by a name.
+    fn is_extern_declaration(source: &mut TokenStream<'a>) -> bool {
+        source.peek_token() == Some(&Token::Ident("extern"))
+            && matches!(source.peek_nth_token(2), Some(Token::Ident(_)))
+    }
+    fn parse_extern(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        let start = parse_token(source, Token::Ident("extern"))?.start;
+        let names = <&str>::parse_group(source, Token::Comma, Token::Semicolon)?.0;
+        Ok(Chunk::new(
+            Self::Extern(names),
+            Span {
+                start,
+                end: *source.pos(),
+            },
+        ))
+    }
+
+    fn parse_while(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        let start = parse_token(source, Token::KeywordWhile)?.start;
+        let cond = Expression::parse(source)?;
+        parse_token(source, Token::BraceOpen)?;
+        let body = Block::parse(source)?;
+        let end = parse_token(source, Token::BraceClose)?.end;
+        Ok(Chunk::new(Self::While { cond, body }, Span { start, end }))
+    }
+
     fn parse_for(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
         let start = parse_token(source, Token::KeywordFor)?.start;
         let name = <&str>::parse(source)?;
@@ -56,8 +130,14 @@ impl<'a> Statement<'a> {
 
 impl<'a> Parse<'a> for Statement<'a> {
     fn parse(source: &mut TokenStream<'a>) -> Result<Chunk<Self>> {
+        if Self::is_type_declaration(source) {
+            return Self::parse_type(source);
+        } else if Self::is_extern_declaration(source) {
+            return Self::parse_extern(source);
+        }
         match source.peek_token() {
             Some(Token::KeywordFor) => Self::parse_for(source),
+            Some(Token::KeywordWhile) => Self::parse_while(source),
             Some(Token::KeywordReturn) => {
                 let Some(Ok(Chunk {
                     span: Span { start, .. },
@@ -72,6 +152,22 @@ impl<'a> Parse<'a> for Statement<'a> {
             }
             Some(_) => {
                 let expr = Expression::parse(source)?;
+                let ty = if let Some(Token::Colon) = source.peek_token() {
+                    let colon = parse_token(source, Token::Colon)?;
+                    if !matches!(expr.data, Expression::Variable(_)) {
+                        return Err(Error::new(
+                            "Only variables can have type annotations.",
+                            colon,
+                        ));
+                    }
+                    let ty = <&str>::parse(source)?;
+                    if source.peek_token() != Some(&Token::Equals) {
+                        parse_token(source, Token::Equals)?;
+                    }
+                    Some(ty)
+                } else {
+                    None
+                };
                 if let Some(
                     Token::Equals
                     | Token::PlusEquals
@@ -108,6 +204,7 @@ impl<'a> Parse<'a> for Statement<'a> {
                                 op_span,
                             ),
                             value,
+                            ty,
                         },
                         span,
                     ))
@@ -0,0 +1,272 @@
+use std::{iter::Peekable, rc::Rc, str::Chars};
+
+use lib::{
+    error::{Error, Result},
+    interface,
+    span::{Chunk, Span},
+    type_error,
+    value::{Call, Value},
+};
+
+interface!(FmtLib {
+    format: Format(Output::String),
+    print: Format(Output::Print),
+    println: Format(Output::PrintLine),
+});
+
+#[derive(Clone, Copy)]
+pub enum Output {
+    String,
+    Print,
+    PrintLine,
+}
+
+/// A variadic formatting function. The first argument is the format string, and the
+/// rest are substituted into its placeholders. Named placeholders (`{name}`) are looked
+/// up in the last argument, which must be a map.
+pub struct Format(Output);
+impl Call for Format {
+    fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+        let mut args = args.into_iter();
+        let template = match args.next() {
+            Some(Chunk {
+                data: Value::String(str),
+                ..
+            }) => str,
+            Some(v) => return Err(Error::new(type_error!("string", v.data.type_of()), v.span)),
+            None => return Err(Error::new("Expected a format string.", span)),
+        };
+        let args = args.map(|arg| arg.data).collect::<Vec<_>>();
+        let text = format(&template, &args, span)?;
+        Ok(match self.0 {
+            Output::String => Value::String(text),
+            Output::Print => {
+                print!("{text}");
+                Value::Null
+            }
+            Output::PrintLine => {
+                println!("{text}");
+                Value::Null
+            }
+        })
+    }
+}
+impl From<Format> for Value {
+    fn from(value: Format) -> Self {
+        Value::Function(Rc::new(value))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    zero: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+    debug: bool,
+}
+
+pub fn format(template: &str, args: &[Value], span: Span) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    let mut next_positional = 0;
+    let mut offset = 0;
+
+    while let Some(c) = chars.next() {
+        offset += 1;
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                offset += 1;
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                offset += 1;
+                out.push('}');
+            }
+            '}' => {
+                return Err(Error::new(
+                    format!("Unmatched '}}' at position {offset} in format string."),
+                    span,
+                ));
+            }
+            '{' => {
+                let start = offset;
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => {
+                            return Err(Error::new(
+                                format!("Unmatched '{{' at position {start} in format string."),
+                                span,
+                            ));
+                        }
+                    }
+                }
+                offset += placeholder.chars().count() + 1;
+
+                let (name, spec) = placeholder
+                    .split_once(':')
+                    .unwrap_or((placeholder.as_str(), ""));
+                let value = if name.is_empty() {
+                    next_positional += 1;
+                    positional(args, next_positional - 1, span)?
+                } else if let Ok(index) = name.parse::<usize>() {
+                    positional(args, index, span)?
+                } else {
+                    named(args, name, span)?
+                };
+                let spec = parse_spec(spec).ok_or_else(|| {
+                    Error::new(
+                        format!("Invalid format spec '{{{placeholder}}}' at position {start}."),
+                        span,
+                    )
+                })?;
+                write_value(&mut out, &value, &spec, span)?;
+            }
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
+
+fn positional(args: &[Value], index: usize, span: Span) -> Result<Value> {
+    args.get(index).cloned().ok_or_else(|| {
+        Error::new(
+            format!(
+                "The format string uses argument {index}, but only {len} were given.",
+                len = args.len()
+            ),
+            span,
+        )
+    })
+}
+
+fn named(args: &[Value], name: &str, span: Span) -> Result<Value> {
+    let value = match args.last() {
+        Some(Value::Map(map)) => map.get(name).cloned(),
+        Some(Value::MapRef(map)) => map.borrow().get(name).cloned(),
+        Some(v) => {
+            return Err(Error::new(
+                format!(
+                    "Named argument '{name}' requires a map as the last argument, but found {}.",
+                    v.type_of()
+                ),
+                span,
+            ));
+        }
+        None => None,
+    };
+    value.ok_or_else(|| Error::new(format!("No named argument '{name}'."), span))
+}
+
+fn parse_spec(spec: &str) -> Option<Spec> {
+    fn align(c: char) -> Option<Align> {
+        match c {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            _ => None,
+        }
+    }
+    fn number(chars: &mut Peekable<Chars>) -> Option<usize> {
+        let mut digits = String::new();
+        while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(*c);
+            chars.next();
+        }
+        digits.parse().ok()
+    }
+
+    let mut result = Spec {
+        fill: ' ',
+        align: None,
+        zero: false,
+        width: None,
+        precision: None,
+        debug: false,
+    };
+
+    let mut rest = spec;
+    let mut lookahead = spec.chars();
+    if let (Some(fill), Some(a)) = (lookahead.next(), lookahead.next())
+        && align(a).is_some()
+    {
+        result.fill = fill;
+        result.align = align(a);
+        rest = &spec[fill.len_utf8() + 1..];
+    } else if let Some(a) = spec.chars().next().and_then(align) {
+        result.align = Some(a);
+        rest = &spec[1..];
+    }
+    if let Some(stripped) = rest.strip_prefix('0') {
+        result.zero = true;
+        rest = stripped;
+    }
+
+    let mut chars = rest.chars().peekable();
+    result.width = number(&mut chars);
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        result.precision = Some(number(&mut chars)?);
+    }
+    if chars.peek() == Some(&'?') {
+        chars.next();
+        result.debug = true;
+    }
+
+    chars.next().is_none().then_some(result)
+}
+
+fn write_value(out: &mut String, value: &Value, spec: &Spec, span: Span) -> Result<()> {
+    let numeric = matches!(value, Value::Int(_) | Value::Float(_));
+    let text = match (value, spec.precision) {
+        _ if spec.debug => value.structure(),
+        (Value::Float(float), Some(precision)) => format!("{float:.precision$}"),
+        (Value::String(str), Some(precision)) => str.chars().take(precision).collect(),
+        _ => value.stringify(span)?,
+    };
+
+    let len = text.chars().count();
+    let width = spec.width.unwrap_or(0);
+    if len >= width {
+        out.push_str(&text);
+        return Ok(());
+    }
+    let padding = width - len;
+
+    if spec.zero && numeric && spec.align.is_none() {
+        let (sign, digits) = text
+            .strip_prefix('-')
+            .map_or(("", text.as_str()), |digits| ("-", digits));
+        out.push_str(sign);
+        out.extend(std::iter::repeat_n('0', padding));
+        out.push_str(digits);
+        return Ok(());
+    }
+
+    let default = if numeric { Align::Right } else { Align::Left };
+    let (before, after) = match spec.align.unwrap_or(default) {
+        Align::Left => (0, padding),
+        Align::Right => (padding, 0),
+        Align::Center => (padding / 2, padding - padding / 2),
+    };
+    out.extend(std::iter::repeat_n(spec.fill, before));
+    out.push_str(&text);
+    out.extend(std::iter::repeat_n(spec.fill, after));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
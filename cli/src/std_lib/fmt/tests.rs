@@ -0,0 +1,139 @@
+use std::{collections::HashMap, fmt::Debug};
+
+use lib::{
+    span::{Chunk, Pos, Span},
+    value::{Call, Value},
+};
+
+use super::{Format, Output, format};
+
+fn span() -> Span {
+    Span {
+        start: Pos { col: 4, ln: 2 },
+        end: Pos { col: 9, ln: 2 },
+    }
+}
+
+fn fmt(template: &str, args: &[Value]) -> String {
+    format(template, args, span()).unwrap()
+}
+
+fn error(template: &str, args: &[Value]) -> String {
+    let error = format(template, args, span()).unwrap_err();
+    assert_eq!(error.span(), span());
+    format!("{error:?}")
+}
+
+fn assert_contains(text: impl Debug, part: &str) {
+    let text = format!("{text:?}");
+    assert!(text.contains(part), "{text}");
+}
+
+#[test]
+fn placeholders_are_filled_in_order() {
+    assert_eq!(
+        fmt("{} and {}", &[Value::Int(1), Value::from("two")]),
+        "1 and two"
+    );
+}
+
+#[test]
+fn placeholders_can_pick_an_argument_by_position() {
+    let args = [Value::from("a"), Value::from("b")];
+    assert_eq!(fmt("{1}{0}{1}", &args), "bab");
+}
+
+#[test]
+fn named_placeholders_come_from_a_map_last() {
+    let map = HashMap::from([(String::from("name"), Value::from("Ada"))]);
+    assert_eq!(fmt("hi {name}", &[Value::Map(map)]), "hi Ada");
+}
+
+#[test]
+fn braces_can_be_escaped() {
+    assert_eq!(fmt("{{}} {{x}}", &[]), "{} {x}");
+}
+
+#[test]
+fn width_pads_text_left_and_numbers_right() {
+    assert_eq!(fmt("[{:5}]", &[Value::from("ab")]), "[ab   ]");
+    assert_eq!(fmt("[{:5}]", &[Value::Int(42)]), "[   42]");
+    // Values longer than the width are left as they are.
+    assert_eq!(fmt("[{:2}]", &[Value::from("long")]), "[long]");
+}
+
+#[test]
+fn alignment_and_fill() {
+    let value = [Value::from("ab")];
+    assert_eq!(fmt("[{:<6}]", &value), "[ab    ]");
+    assert_eq!(fmt("[{:>6}]", &value), "[    ab]");
+    assert_eq!(fmt("[{:^6}]", &value), "[  ab  ]");
+    assert_eq!(fmt("[{:^5}]", &value), "[ ab  ]");
+    assert_eq!(fmt("[{:*>6}]", &value), "[****ab]");
+    assert_eq!(fmt("[{:é<4}]", &value), "[abéé]");
+}
+
+#[test]
+fn zero_padding_goes_after_the_sign() {
+    assert_eq!(fmt("{:05}", &[Value::Int(42)]), "00042");
+    assert_eq!(fmt("{:05}", &[Value::Int(-42)]), "-0042");
+    assert_eq!(fmt("{:08.3}", &[Value::Float(-2.5)]), "-002.500");
+}
+
+#[test]
+fn precision_rounds_floats_and_cuts_strings() {
+    assert_eq!(fmt("{:.2}", &[Value::Float(1.23456)]), "1.23");
+    assert_eq!(fmt("{:.0}", &[Value::Float(2.5)]), "2");
+    assert_eq!(fmt("{:.3}", &[Value::from("abcdef")]), "abc");
+    assert_eq!(fmt("{:>6.2}", &[Value::Float(1.0)]), "  1.00");
+}
+
+#[test]
+fn debug_writes_the_structure() {
+    assert_eq!(fmt("{:?}", &[Value::from("quoted")]), "\"quoted\"");
+    assert_eq!(
+        fmt(
+            "{:?}",
+            &[Value::Array(vec![Value::Int(1), Value::from("two")])]
+        ),
+        "[1, \"two\"]"
+    );
+}
+
+#[test]
+fn malformed_format_strings_are_errors_at_the_call() {
+    assert_contains(error("a } b", &[]), "Unmatched '}' at position 3");
+    assert_contains(error("a {", &[]), "Unmatched '{' at position 3");
+    assert_contains(
+        error("{:x}", &[Value::Int(1)]),
+        "Invalid format spec '{:x}'",
+    );
+    assert_contains(error("{:.}", &[Value::Int(1)]), "Invalid format spec");
+}
+
+#[test]
+fn missing_arguments_are_errors() {
+    assert_contains(
+        error("{} {}", &[Value::Int(1)]),
+        "uses argument 1, but only 1 were given",
+    );
+    assert_contains(error("{name}", &[]), "No named argument 'name'.");
+    assert_contains(
+        error("{name}", &[Value::Int(1)]),
+        "requires a map as the last argument, but found int",
+    );
+}
+
+#[test]
+fn the_format_string_must_be_a_string() {
+    let at = Span {
+        start: Pos { col: 11, ln: 2 },
+        end: Pos { col: 12, ln: 2 },
+    };
+    let error = Format(Output::String)
+        .call(vec![Chunk::new(Value::Int(1), at)], span())
+        .unwrap_err();
+    assert_eq!(error.span(), at);
+    let error = Format(Output::String).call(vec![], span()).unwrap_err();
+    assert_contains(error, "Expected a format string.");
+}
@@ -0,0 +1,15 @@
+use lib::{
+    compat::{context::Context, function::IntoCallable},
+    error::Result,
+    gc::{self, Stats},
+    interface,
+};
+
+interface!(GcLib {
+    collect: collect.into_callable(),
+});
+
+/// Frees maps and functions that are only kept alive by reference cycles.
+pub fn collect(ctx: &Context) -> Result<Stats> {
+    Ok(ctx.tracker().map(gc::collect).unwrap_or_default())
+}
@@ -1,7 +1,8 @@
 use lib::{
-    compat::function::IntoCallable,
-    error::{Error, Result},
+    compat::{context::Context, function::IntoCallable},
+    error::Result,
     interface,
+    value::Value,
 };
 
 interface!(IoLib {
@@ -9,11 +10,11 @@ interface!(IoLib {
     print: print.into_callable(),
 });
 
-pub fn println(text: String) -> Result<()> {
-    println!("{text}");
+pub fn println(ctx: &Context, value: Value) -> Result<()> {
+    println!("{}", value.stringify(ctx.span())?);
     Ok(())
 }
-pub fn print(text: String) -> Result<()> {
-    print!("{text}");
+pub fn print(ctx: &Context, value: Value) -> Result<()> {
+    print!("{}", value.stringify(ctx.span())?);
     Ok(())
 }
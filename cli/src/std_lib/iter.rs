@@ -1,14 +1,29 @@
-use std::{cell::Cell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    vec,
+};
 
 use lib::{
-    compat::{function::IntoCallable, type_name::TypeName},
+    compat::{
+        context::{Context, Host},
+        function::IntoCallable,
+        script_fn::ScriptFn,
+        type_name::TypeName,
+    },
     error::*,
+    gc::Edge,
     interface,
+    span::{Chunk, Span},
+    type_error,
     value::{Call, Value},
 };
 
 interface!(IterLib {
-    range: range.into_callable(),
+    range: range.into_callable().with_names(&["start", "end"]),
+    map: map.into_callable().with_names(&["iter", "func"]),
+    filter: filter.into_callable().with_names(&["iter", "predicate"]),
+    collect: collect.into_callable().with_names(&["iter"]),
 });
 
 pub struct Range {
@@ -16,7 +31,7 @@ pub struct Range {
     end: i32,
 }
 impl Call for Range {
-    fn call(&self, _: Vec<Value>, _: lib::span::Span) -> Result<Value> {
+    fn call(&self, _: Vec<Chunk<Value>>, _: Span) -> Result<Value> {
         let current = self.idx.get();
         Ok(if current >= self.end {
             Value::Null
@@ -25,6 +40,17 @@ impl Call for Range {
             Value::Int(current)
         })
     }
+    // Ranges can be long enough to run past the limits on their own.
+    fn call_from(
+        &self,
+        host: &dyn Host,
+        args: Vec<Chunk<Value>>,
+        _: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        host.step(span)?;
+        self.call(args, span)
+    }
 }
 impl Into<Value> for Range {
     fn into(self) -> Value {
@@ -43,3 +69,151 @@ pub fn range(start: i32, end: i32) -> Result<Range> {
         end,
     })
 }
+
+/// Iterates over the items of an array, so arrays can be used anywhere an iterator
+/// function is expected.
+struct ArrayIter(RefCell<vec::IntoIter<Value>>);
+impl Call for ArrayIter {
+    fn call(&self, _: Vec<Chunk<Value>>, _: Span) -> Result<Value> {
+        Ok(self.0.borrow_mut().next().unwrap_or(Value::Null))
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        if let Ok(items) = self.0.try_borrow() {
+            items.as_slice().iter().for_each(|item| item.trace(visit));
+        }
+    }
+}
+
+/// `span` is the span of the argument `value` was given as.
+fn into_iter(value: Value, span: Span) -> Result<Rc<dyn Call>> {
+    match value {
+        Value::Function(function) => Ok(function),
+        Value::Array(items) => Ok(Rc::new(ArrayIter(RefCell::new(items.into_iter())))),
+        v => Err(Error::new(
+            type_error!("an iterator function", v.type_of()),
+            span,
+        )),
+    }
+}
+
+pub struct Map {
+    iter: Rc<dyn Call>,
+    func: ScriptFn<(Value,), Value>,
+}
+impl Map {
+    fn next(&self, ctx: &Context) -> Result<Value> {
+        ctx.step()?;
+        match ctx.call_function(&self.iter, ())? {
+            Value::Null => Ok(Value::Null),
+            item => self.func.call_in(ctx, (item,)),
+        }
+    }
+}
+impl Call for Map {
+    fn call(&self, _: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+        self.next(&Context::new(None, span, Vec::new()))
+    }
+    // Passes the engine on to the function, for callbacks that take a `Context`.
+    fn call_from(
+        &self,
+        host: &dyn Host,
+        _: Vec<Chunk<Value>>,
+        _: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        self.next(&Context::new(Some(host), span, Vec::new()))
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        visit(Edge::Function(&self.iter));
+        visit(Edge::Function(self.func.function()));
+    }
+}
+impl From<Map> for Value {
+    fn from(map: Map) -> Self {
+        Value::Function(Rc::new(map))
+    }
+}
+impl TypeName for Map {
+    fn type_name() -> String {
+        String::from("std.iter.map")
+    }
+}
+
+pub fn map(ctx: &Context, iter: Value, func: ScriptFn<(Value,), Value>) -> Result<Map> {
+    Ok(Map {
+        iter: into_iter(iter, ctx.arg_span(0))?,
+        func,
+    })
+}
+
+pub struct Filter {
+    iter: Rc<dyn Call>,
+    predicate: ScriptFn<(Value,), bool>,
+}
+impl Filter {
+    fn next(&self, ctx: &Context) -> Result<Value> {
+        loop {
+            ctx.step()?;
+            let item = ctx.call_function(&self.iter, ())?;
+            if matches!(item, Value::Null) {
+                return Ok(Value::Null);
+            }
+            if self.predicate.call_in(ctx, (item.clone(),))? {
+                return Ok(item);
+            }
+        }
+    }
+}
+impl Call for Filter {
+    fn call(&self, _: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+        self.next(&Context::new(None, span, Vec::new()))
+    }
+    fn call_from(
+        &self,
+        host: &dyn Host,
+        _: Vec<Chunk<Value>>,
+        _: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        self.next(&Context::new(Some(host), span, Vec::new()))
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        visit(Edge::Function(&self.iter));
+        visit(Edge::Function(self.predicate.function()));
+    }
+}
+impl From<Filter> for Value {
+    fn from(filter: Filter) -> Self {
+        Value::Function(Rc::new(filter))
+    }
+}
+impl TypeName for Filter {
+    fn type_name() -> String {
+        String::from("std.iter.filter")
+    }
+}
+
+pub fn filter(ctx: &Context, iter: Value, predicate: ScriptFn<(Value,), bool>) -> Result<Filter> {
+    Ok(Filter {
+        iter: into_iter(iter, ctx.arg_span(0))?,
+        predicate,
+    })
+}
+
+pub fn collect(ctx: &Context, iter: Value) -> Result<Vec<Value>> {
+    let iter = into_iter(iter, ctx.arg_span(0))?;
+    let mut items = Vec::new();
+    loop {
+        ctx.step()?;
+        match ctx.call_function(&iter, ())? {
+            Value::Null => return Ok(items),
+            item => {
+                ctx.check_size(items.len() + 1)?;
+                items.push(item);
+            }
+        }
+    }
+}
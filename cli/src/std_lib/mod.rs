@@ -1,27 +1,30 @@
-use std::{cell::RefCell, rc::Rc};
-
+use fmt::FmtLib;
+use gc::GcLib;
 use io::IoLib;
-use lib::module_registry::REGISTRY;
-use prelude::Prelude;
+use process::ProcessLib;
+
+use crate::engine::{Capability, Engine};
 
+pub mod fmt;
 pub mod fs;
+pub mod gc;
 pub mod io;
 pub mod iter;
 pub mod prelude;
 pub mod primatives;
+pub mod process;
 pub mod types;
 
-thread_local!(pub static PRELUDE: Rc<RefCell<Prelude>> = Rc::new(RefCell::new(Prelude::new())));
-pub fn init_registry() {
-    REGISTRY.with_borrow_mut(|reg| {
-        PRELUDE.with(|prelude| {
-            let prelude = prelude.borrow();
-            reg.insert("std/iter", prelude.iter.clone());
-            reg.insert("std/int", prelude.int.clone());
-            reg.insert("std/map", prelude.map.clone());
-            reg.insert("std/type", prelude.r#type.clone());
-        });
-        reg.insert("std/io", IoLib::new());
-        //reg.insert("std/fs", FileLib::new());
-    })
+/// Registers the standard library modules with an engine.
+pub fn register(engine: &Engine) {
+    let prelude = engine.prelude().borrow();
+    engine.register_module("std/iter", prelude.iter.clone());
+    engine.register_module("std/int", prelude.int.clone());
+    engine.register_module("std/map", prelude.map.clone());
+    engine.register_module("std/type", prelude.r#type.clone());
+    engine.register_module("std/io", IoLib::new());
+    engine.register_module("std/fmt", FmtLib::new());
+    engine.register_module("std/gc", GcLib::new());
+    engine.register_privileged_module("std/process", Capability::Process, ProcessLib::new());
+    //engine.register_privileged_module("std/fs", Capability::FileSystem, FileLib::new());
 }
@@ -2,7 +2,7 @@ use lib::{compat::function::IntoCallable, interface};
 
 use super::{
     io, iter,
-    primatives::{IntLib, Map},
+    primatives::{self, IntLib, Map},
     types::{self, TypeLib},
 };
 
@@ -14,4 +14,5 @@ interface!(Prelude {
 
     println: io::println.into_callable(),
     print: io::print.into_callable(),
+    str: primatives::to_str.into_callable(),
 });
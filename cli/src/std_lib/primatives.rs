@@ -1,7 +1,7 @@
 use std::{cell::RefCell, rc::Rc};
 
 use lib::{
-    compat::function::IntoCallable,
+    compat::{context::Context, function::IntoCallable},
     error::*,
     interface,
     value::{MapRef, Value},
@@ -15,6 +15,10 @@ fn int_to_str(int: i32) -> Result<String> {
     Ok(int.to_string())
 }
 
+pub fn to_str(ctx: &Context, value: Value) -> Result<String> {
+    value.stringify(ctx.span())
+}
+
 interface!(Map {
     keys: map_keys.into_callable(),
     has: map_has.into_callable(),
@@ -0,0 +1,17 @@
+use std::{env, process};
+
+use lib::{compat::function::IntoCallable, error::Result, interface, value::Value};
+
+interface!(ProcessLib {
+    exit: exit.into_callable().with_names(&["code"]),
+    env: env.into_callable().with_names(&["name"]),
+});
+
+pub fn exit(code: i32) -> Result<()> {
+    process::exit(code)
+}
+
+/// The value of an environment variable, or null if it isn't set.
+pub fn env(name: String) -> Result<Value> {
+    Ok(env::var(name).map_or(Value::Null, Value::String))
+}
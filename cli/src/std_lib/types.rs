@@ -1,20 +1,174 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    ptr,
+    rc::{Rc, Weak},
+};
+
 use lib::{
-    compat::function::IntoCallable, error::*, interface, span::Span, type_error, value::Value,
+    compat::{context::Context, function::IntoCallable},
+    error::*,
+    interface, metakeys,
+    span::{Chunk, Span},
+    type_error,
+    value::{Call, MapRef, Value},
 };
 
 interface!(TypeLib {
     of: type_of.into_callable(),
-    assert: assert.into_callable(),
+    assert: assert.into_callable().with_names(&["value", "expected"]),
+    is: is.into_callable().with_names(&["value", "type"]),
 });
 
 pub fn type_of(value: Value) -> Result<String> {
     Ok(value.type_of())
 }
-pub fn assert(value: Value, expected: String) -> Result<()> {
+pub fn assert(ctx: &Context, value: Value, expected: String) -> Result<()> {
     let vtype = type_of(value)?;
     if vtype != expected {
-        Err(Error::new(type_error!(expected, vtype), Span::default()))
+        Err(Error::new(type_error!(expected, vtype), ctx.arg_span(0)))
     } else {
         Ok(())
     }
 }
+
+/// The type names [`is_type`] understands without a record type of the same name.
+pub const BUILT_IN_TYPES: [&str; 10] = [
+    "*", "null", "string", "int", "float", "bool", "function", "array", "map", "userdata",
+];
+
+/// Whether `value` is of the type named `expected`. Values with a custom type name also
+/// count as their primative type, and `*` matches anything.
+pub fn is_type(value: &Value, expected: &str) -> bool {
+    expected == "*" || value.type_of() == expected || value.primative_type() == expected
+}
+
+/// Whether `value` is of the type `ty`, which is either a type name or a record type.
+/// Only records made by the record type count, not maps that look like them.
+pub fn is(ctx: &Context, value: Value, ty: Value) -> Result<bool> {
+    let record_type = match &ty {
+        Value::String(name) => return Ok(is_type(&value, name)),
+        Value::Function(function) => function.as_any().and_then(|f| f.downcast_ref()),
+        _ => None,
+    };
+    let Some(record_type) = record_type else {
+        return Err(Error::new(
+            type_error!("a type name or record type", ty.type_of()),
+            ctx.arg_span(1),
+        ));
+    };
+    Ok(match &value {
+        Value::MapRef(map) => map
+            .borrow()
+            .as_any()
+            .and_then(|map| map.downcast_ref::<Record>())
+            .is_some_and(|record| ptr::eq(&*record.ty, record_type)),
+        _ => false,
+    })
+}
+
+/// The constructor created by a `type` declaration.
+#[derive(Debug)]
+pub struct RecordType {
+    name: String,
+    fields: Vec<(String, String)>,
+    this: Weak<RecordType>,
+}
+
+impl RecordType {
+    pub fn new(name: String, fields: Vec<(String, String)>) -> Rc<Self> {
+        Rc::new_cyclic(|this| Self {
+            name,
+            fields,
+            this: this.clone(),
+        })
+    }
+
+    fn check_field(&self, name: &str, value: &Value) -> Result<()> {
+        let Some((_, ty)) = self.fields.iter().find(|(field, _)| field == name) else {
+            return Err(Error::new(
+                format!("The type {} has no field '{name}'.", self.name),
+                Span::default(),
+            ));
+        };
+        if is_type(value, ty) {
+            Ok(())
+        } else {
+            Err(Error::new(
+                type_error!(ty, value.type_of()),
+                Span::default(),
+            ))
+        }
+    }
+}
+
+impl Call for RecordType {
+    fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+        if args.len() != self.fields.len() {
+            return Err(Error::new(
+                format!(
+                    "The type {} has {} field{}, but got {} arguments.",
+                    self.name,
+                    self.fields.len(),
+                    if self.fields.len() == 1 { "" } else { "s" },
+                    args.len()
+                ),
+                span,
+            ));
+        }
+
+        let mut fields = HashMap::new();
+        fields.insert(
+            metakeys::TYPE_NAME.to_string(),
+            Value::String(self.name.clone()),
+        );
+        fields.insert(
+            metakeys::CONSTRUCTOR.to_string(),
+            Value::Function(self.this.upgrade().unwrap()),
+        );
+        for ((name, _), arg) in self.fields.iter().zip(args) {
+            self.check_field(name, &arg.data)
+                .map_err(|e| e.with_span(arg.span))?;
+            fields.insert(name.clone(), arg.data);
+        }
+
+        Ok(Value::MapRef(Rc::new(RefCell::new(Record {
+            ty: self.this.upgrade().unwrap(),
+            fields,
+        }))))
+    }
+
+    fn parameter_names(&self) -> Option<Vec<&str>> {
+        Some(self.fields.iter().map(|(name, _)| name.as_str()).collect())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// An instance of a [`RecordType`]. Only declared fields can be set, and only to values of
+/// the declared type.
+#[derive(Debug)]
+pub struct Record {
+    ty: Rc<RecordType>,
+    fields: HashMap<String, Value>,
+}
+
+impl MapRef for Record {
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.fields.get(name)
+    }
+    fn set(&mut self, name: String, val: Value) -> Result<()> {
+        self.ty.check_field(&name, &val)?;
+        self.fields.insert(name, val);
+        Ok(())
+    }
+    fn as_hashmap(&self) -> Option<&HashMap<String, Value>> {
+        Some(&self.fields)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
@@ -0,0 +1,33 @@
+use std::{
+    env, fs,
+    process::{self, Command, Stdio},
+};
+
+/// Runs `foliose check` on `code`, returning whether it passed.
+fn check(name: &str, code: &str) -> bool {
+    let path = env::temp_dir().join(format!("foliose-check-{}-{name}.fol", process::id()));
+    fs::write(&path, code).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_foliose"))
+        .arg("check")
+        .arg(&path)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    status.success()
+}
+
+#[test]
+fn check_succeeds_for_a_well_typed_module() {
+    assert!(check("ok", "count: int = 1;"));
+}
+
+#[test]
+fn check_fails_for_a_type_error() {
+    assert!(!check("mismatch", "count: int = \"one\";"));
+}
+
+#[test]
+fn check_fails_for_a_syntax_error() {
+    assert!(!check("syntax", "count: int = ;"));
+}
@@ -0,0 +1,45 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use foliose::engine::Engine;
+use lib::{error::ErrorKind, value::Value};
+
+#[test]
+fn interrupting_from_another_thread_stops_a_run() {
+    let engine = Engine::new();
+    let interrupt = engine.interrupt_handle();
+    let done = Arc::new(AtomicBool::new(false));
+    // Interrupts until the run has stopped, in case the first one lands before it starts.
+    let interrupter = thread::spawn({
+        let done = done.clone();
+        move || {
+            while !done.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(10));
+                interrupt.interrupt();
+            }
+        }
+    });
+    let result = engine.run("while true {}");
+    done.store(true, Ordering::Relaxed);
+    interrupter.join().unwrap();
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Interrupted);
+    assert_eq!(engine.run("return 1;").unwrap(), Value::from(1));
+}
+
+#[test]
+fn hosts_can_collect_cycles_between_runs() {
+    let engine = Engine::new();
+    engine
+        .run("make = fn() -> { node = #{}; node.self = node; return null; }; make();")
+        .unwrap();
+    // `node`, and the top level scope, which `make` refers back to.
+    assert_eq!(engine.collect_garbage().collected, 2);
+    assert_eq!(engine.collect_garbage().collected, 0);
+}
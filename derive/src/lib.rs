@@ -0,0 +1,319 @@
+//! Macros for exposing Rust types to scripts as user data. They are re-exported by
+//! `foliose-lib`, which the code they generate refers to as `::foliose_lib`. Crates that
+//! depend on it under another name can say so with `#[foliose(crate = "name")]`.
+
+use std::collections::HashSet;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Attribute, Data, DataStruct, DeriveInput, Error, FnArg, ImplItem, ItemImpl, LitStr, Pat, Path,
+    Result, Type, Visibility, ext::IdentExt, meta::ParseNestedMeta, parse_macro_input, parse_quote,
+};
+
+/// Options given with `#[foliose(...)]`.
+#[derive(Default)]
+struct Options {
+    krate: Option<Path>,
+    name: Option<String>,
+    skip: bool,
+    readonly: bool,
+    constructor: bool,
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("foliose")) {
+            attr.parse_nested_meta(|meta| options.set(meta))?;
+        }
+        Ok(options)
+    }
+
+    fn set(&mut self, meta: ParseNestedMeta) -> Result<()> {
+        if meta.path.is_ident("crate") {
+            self.krate = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+        } else if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("skip") {
+            self.skip = true;
+        } else if meta.path.is_ident("readonly") {
+            self.readonly = true;
+        } else if meta.path.is_ident("constructor") {
+            self.constructor = true;
+        } else {
+            return Err(meta.error("unknown foliose option"));
+        }
+        Ok(())
+    }
+
+    fn krate(&self) -> Path {
+        self.krate
+            .clone()
+            .unwrap_or_else(|| parse_quote!(::foliose_lib))
+    }
+}
+
+/// Exposes the named fields of a struct as properties that scripts can read and write,
+/// converting values with `Into<Value>` and `FromValue`. Fields are read by cloning them.
+///
+/// The struct can be given a type name with `#[foliose(name = "...")]`. Fields can be
+/// renamed with `#[foliose(name = "...")]`, hidden with `#[foliose(skip)]` and made
+/// read only with `#[foliose(readonly)]`.
+#[proc_macro_derive(FolioseType, attributes(foliose))]
+pub fn derive_foliose_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn derive(input: DeriveInput) -> Result<TokenStream2> {
+    let options = Options::parse(&input.attrs)?;
+    let krate = options.krate();
+    let ident = &input.ident;
+    let type_name = options.name.unwrap_or_else(|| ident.unraw().to_string());
+    let Data::Struct(DataStruct {
+        fields: syn::Fields::Named(fields),
+        ..
+    }) = &input.data
+    else {
+        return Err(Error::new_spanned(
+            ident,
+            "FolioseType can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut getters = Vec::new();
+    let mut setters = Vec::new();
+    let mut keys = HashSet::new();
+    for field in &fields.named {
+        let options = Options::parse(&field.attrs)?;
+        if options.skip {
+            continue;
+        }
+        let field = field.ident.as_ref().unwrap();
+        let key = options.name.unwrap_or_else(|| field.unraw().to_string());
+        if !keys.insert(key.clone()) {
+            return Err(Error::new_spanned(
+                field,
+                format!("there is already a property called `{key}`"),
+            ));
+        }
+        getters.push(quote! {
+            #key => Some(::std::convert::Into::into(::std::clone::Clone::clone(&self.#field)))
+        });
+        setters.push(if options.readonly {
+            quote! {
+                #key => Some(Err(#krate::error::Error::new(
+                    ::std::format!("The property '{}' can't be changed.", #key),
+                    span,
+                )))
+            }
+        } else {
+            quote! {
+                #key => Some(
+                    #krate::compat::from_value::FromValue::from_value(value, span)
+                        .map(|value| self.#field = value)
+                )
+            }
+        });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::compat::type_name::TypeName for #ident #ty_generics #where_clause {
+            fn type_name() -> ::std::string::String {
+                ::std::string::String::from(#type_name)
+            }
+        }
+
+        impl #impl_generics #krate::compat::user_data::Fields for #ident #ty_generics #where_clause {
+            fn get_field(&self, name: &str) -> ::std::option::Option<#krate::value::Value> {
+                match name {
+                    #(#getters,)*
+                    _ => None,
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn set_field(
+                &mut self,
+                name: &str,
+                value: #krate::value::Value,
+                span: #krate::span::Span,
+            ) -> ::std::option::Option<#krate::error::Result<()>> {
+                match name {
+                    #(#setters,)*
+                    _ => None,
+                }
+            }
+        }
+    })
+}
+
+/// Implements `UserType` using the public functions of an impl block. Functions that
+/// take `&self` or `&mut self` become methods, and the rest become static functions,
+/// which make up the type's module. The constructor is the function marked with
+/// `#[foliose(constructor)]`, or else the one called `new`.
+///
+/// Functions must return `Result`. Static functions can take a `&Context` first, which
+/// isn't one of their parameters as far as scripts are concerned; methods can't take one.
+/// Functions can be renamed with `#[foliose(name = "...")]` and hidden with
+/// `#[foliose(skip)]`.
+#[proc_macro_attribute]
+pub fn methods(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = Options::default();
+    let parser = syn::meta::parser(|meta| options.set(meta));
+    parse_macro_input!(args with parser);
+    let item = parse_macro_input!(input as ItemImpl);
+    methods_impl(options, item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn methods_impl(options: Options, mut item: ItemImpl) -> Result<TokenStream2> {
+    let krate = options.krate();
+    if !item.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &item.generics,
+            "foliose methods can't be generic",
+        ));
+    }
+    let self_ty = &item.self_ty;
+
+    let mut methods = Vec::new();
+    let mut statics = Vec::new();
+    // Methods and static functions are looked up in different tables.
+    let mut method_keys = HashSet::new();
+    let mut static_keys = HashSet::new();
+    let mut constructor = None;
+    let mut new = None;
+    for impl_item in &mut item.items {
+        let ImplItem::Fn(function) = impl_item else {
+            continue;
+        };
+        let options = Options::parse(&function.attrs)?;
+        function
+            .attrs
+            .retain(|attr| !attr.path().is_ident("foliose"));
+        if options.skip || !matches!(function.vis, Visibility::Public(_)) {
+            continue;
+        }
+
+        let mut names = Vec::new();
+        let mut named = true;
+        let mut method = false;
+        for (i, input) in function.sig.inputs.iter().enumerate() {
+            match input {
+                FnArg::Receiver(receiver) if receiver.reference.is_none() => {
+                    return Err(Error::new_spanned(
+                        receiver,
+                        "methods must take `&self` or `&mut self`",
+                    ));
+                }
+                FnArg::Receiver(_) => {
+                    method = true;
+                    names.push(String::from("self"));
+                }
+                FnArg::Typed(arg) if is_context(&arg.ty) => {
+                    if method {
+                        return Err(Error::new_spanned(arg, "methods can't take a `&Context`"));
+                    }
+                    if i != 0 {
+                        return Err(Error::new_spanned(
+                            arg,
+                            "a `&Context` must be the first parameter",
+                        ));
+                    }
+                }
+                FnArg::Typed(arg) => match &*arg.pat {
+                    Pat::Ident(pat) => names.push(pat.ident.unraw().to_string()),
+                    _ => named = false,
+                },
+            }
+        }
+
+        let ident = &function.sig.ident;
+        let key = options.name.unwrap_or_else(|| ident.unraw().to_string());
+        let keys = if method {
+            &mut method_keys
+        } else {
+            &mut static_keys
+        };
+        if !keys.insert(key.clone()) {
+            return Err(Error::new_spanned(
+                ident,
+                format!("there is already a function called `{key}`"),
+            ));
+        }
+        let mut callable = quote! {
+            #krate::compat::function::IntoCallable::into_callable(<#self_ty>::#ident)
+        };
+        if named {
+            callable = quote!(#callable.with_names(&[#(#names),*]));
+        }
+        let value = quote!(::std::convert::Into::<#krate::value::Value>::into(#callable));
+        if method {
+            methods.push((key, value));
+            continue;
+        }
+        if options.constructor {
+            constructor = Some(value.clone());
+        } else if key == "new" {
+            new = Some(value.clone());
+        }
+        statics.push(quote!((::std::string::String::from(#key), #value)));
+    }
+
+    let constructor = constructor.or(new).map(|value| {
+        quote! {
+            fn constructor() -> ::std::option::Option<#krate::value::Value> {
+                Some(#value)
+            }
+        }
+    });
+    let (keys, values): (Vec<_>, Vec<_>) = methods.into_iter().unzip();
+    Ok(quote! {
+        #item
+
+        impl #krate::compat::user_data::UserType for #self_ty {
+            fn methods() -> ::std::rc::Rc<
+                ::std::collections::HashMap<::std::string::String, #krate::value::Value>,
+            > {
+                ::std::rc::Rc::new(::std::collections::HashMap::from([
+                    #((::std::string::String::from(#keys), #values)),*
+                ]))
+            }
+
+            fn method(name: &str) -> ::std::option::Option<#krate::value::Value> {
+                match name {
+                    #(#keys => ::std::option::Option::Some(#values),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn statics() -> ::std::collections::HashMap<::std::string::String, #krate::value::Value> {
+                ::std::collections::HashMap::from([#(#statics),*])
+            }
+
+            #constructor
+        }
+    })
+}
+
+/// Whether a parameter is `&Context`, which native functions are given rather than taking
+/// it as an argument.
+fn is_context(ty: &Type) -> bool {
+    let Type::Reference(reference) = ty else {
+        return false;
+    };
+    matches!(
+        &*reference.elem,
+        Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Context")
+    )
+}
+
+#[cfg(test)]
+mod tests;
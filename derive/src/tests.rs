@@ -0,0 +1,292 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{DeriveInput, ItemImpl, parse_quote};
+
+use super::{Options, derive, methods_impl};
+
+/// Whether the generated `code` contains the tokens of `part`.
+fn contains(code: &TokenStream2, part: TokenStream2) -> bool {
+    code.to_string().contains(&part.to_string())
+}
+
+fn methods(item: ItemImpl) -> Result<TokenStream2, String> {
+    methods_impl(Options::default(), item).map_err(|e| e.to_string())
+}
+
+/// The body of the generated `UserType::constructor`.
+fn constructor(code: &TokenStream2) -> String {
+    let code = code.to_string();
+    code.split_once("fn constructor")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default()
+}
+
+fn point() -> DeriveInput {
+    parse_quote! {
+        #[foliose(crate = "lib", name = "Vec2")]
+        struct Point {
+            #[foliose(name = "across")]
+            x: i32,
+            #[foliose(readonly)]
+            y: i32,
+            #[foliose(skip)]
+            z: i32,
+        }
+    }
+}
+
+#[test]
+fn the_crate_path_defaults_to_foliose_lib() {
+    let code = derive(parse_quote!(
+        struct Point {
+            x: i32,
+        }
+    ))
+    .unwrap();
+    assert!(contains(
+        &code,
+        quote!(::foliose_lib::compat::type_name::TypeName)
+    ));
+    let code = derive(point()).unwrap();
+    assert!(contains(
+        &code,
+        quote!(impl lib::compat::type_name::TypeName for Point)
+    ));
+    assert!(!code.to_string().contains("foliose_lib"));
+}
+
+#[test]
+fn the_type_can_be_renamed() {
+    let code = derive(point()).unwrap();
+    assert!(contains(&code, quote!(::std::string::String::from("Vec2"))));
+    let code = derive(parse_quote!(
+        struct Point {
+            x: i32,
+        }
+    ))
+    .unwrap();
+    assert!(contains(
+        &code,
+        quote!(::std::string::String::from("Point"))
+    ));
+}
+
+#[test]
+fn fields_can_be_renamed() {
+    let code = derive(point()).unwrap();
+    assert!(contains(&code, quote!("across" => Some)));
+    assert!(!contains(&code, quote!("x" =>)));
+}
+
+#[test]
+fn skipped_fields_are_left_out() {
+    let code = derive(point()).unwrap();
+    assert!(!contains(&code, quote!(self.z)));
+    assert!(!contains(&code, quote!("z")));
+}
+
+#[test]
+fn readonly_fields_cant_be_set() {
+    let code = derive(point()).unwrap();
+    assert!(contains(
+        &code,
+        quote!(::std::format!("The property '{}' can't be changed.", "y"))
+    ));
+    assert!(contains(&code, quote!(.map(|value| self.x = value))));
+    assert!(!contains(&code, quote!(self.y = value)));
+}
+
+#[test]
+fn only_structs_with_named_fields_can_be_derived() {
+    let error = derive(parse_quote!(
+        struct Pair(i32, i32);
+    ))
+    .unwrap_err();
+    assert!(error.to_string().contains("structs with named fields"));
+}
+
+#[test]
+fn unknown_options_are_rejected() {
+    let error = derive(parse_quote! {
+        #[foliose(hidden)]
+        struct Point { x: i32 }
+    })
+    .unwrap_err();
+    assert_eq!(error.to_string(), "unknown foliose option");
+}
+
+#[test]
+fn two_fields_with_the_same_name_are_rejected() {
+    let error = derive(parse_quote! {
+        struct Point {
+            x: i32,
+            #[foliose(name = "x")]
+            y: i32,
+        }
+    })
+    .unwrap_err();
+    assert_eq!(error.to_string(), "there is already a property called `x`");
+    // Skipped fields don't take up a name.
+    derive(parse_quote! {
+        struct Point {
+            x: i32,
+            #[foliose(skip, name = "x")]
+            y: i32,
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn the_constructor_is_marked_or_called_new() {
+    let code = methods(parse_quote! {
+        impl Point {
+            pub fn new() -> Result<Point> { todo!() }
+            #[foliose(constructor)]
+            pub fn origin() -> Result<Point> { todo!() }
+        }
+    })
+    .unwrap();
+    assert!(constructor(&code).contains(&quote!(<Point>::origin).to_string()));
+
+    let code = methods(parse_quote! {
+        impl Point {
+            pub fn new() -> Result<Point> { todo!() }
+        }
+    })
+    .unwrap();
+    assert!(constructor(&code).contains(&quote!(<Point>::new).to_string()));
+
+    let code = methods(parse_quote! {
+        impl Point {
+            pub fn origin() -> Result<Point> { todo!() }
+        }
+    })
+    .unwrap();
+    assert!(!code.to_string().contains("fn constructor"));
+}
+
+#[test]
+fn functions_can_be_renamed_and_skipped() {
+    let code = methods(parse_quote! {
+        impl Point {
+            #[foliose(name = "length")]
+            pub fn len(&self) -> Result<i32> { todo!() }
+            #[foliose(skip)]
+            pub fn hidden(&self) -> Result<i32> { todo!() }
+            fn private(&self) -> Result<i32> { todo!() }
+        }
+    })
+    .unwrap();
+    assert!(contains(
+        &code,
+        quote!("length" => ::std::option::Option::Some)
+    ));
+    assert!(!contains(&code, quote!("len")));
+    assert!(!contains(&code, quote!(<Point>::hidden)));
+    assert!(!contains(&code, quote!(<Point>::private)));
+    // The attributes are removed from the impl block.
+    assert!(!contains(&code, quote!(#[foliose(skip)])));
+}
+
+#[test]
+fn the_crate_path_can_be_given_to_methods() {
+    let options = Options {
+        krate: Some(parse_quote!(lib)),
+        ..Options::default()
+    };
+    let code = methods_impl(options, parse_quote!(impl Point {})).unwrap();
+    assert!(contains(
+        &code,
+        quote!(impl lib::compat::user_data::UserType for Point)
+    ));
+}
+
+#[test]
+fn static_functions_can_take_a_context_first() {
+    let code = methods(parse_quote! {
+        impl Point {
+            pub fn load(ctx: &Context, path: String) -> Result<Point> { todo!() }
+        }
+    })
+    .unwrap();
+    // The context isn't one of the parameters scripts see.
+    assert!(contains(&code, quote!(with_names(&["path"]))));
+}
+
+#[test]
+fn methods_cant_take_a_context() {
+    let error = methods(parse_quote! {
+        impl Point {
+            pub fn draw(&self, ctx: &Context) -> Result<()> { todo!() }
+        }
+    })
+    .unwrap_err();
+    assert_eq!(error, "methods can't take a `&Context`");
+}
+
+#[test]
+fn a_context_must_come_first() {
+    let error = methods(parse_quote! {
+        impl Point {
+            pub fn load(path: String, ctx: &Context) -> Result<Point> { todo!() }
+        }
+    })
+    .unwrap_err();
+    assert_eq!(error, "a `&Context` must be the first parameter");
+}
+
+#[test]
+fn methods_must_borrow_self() {
+    let error = methods(parse_quote! {
+        impl Point {
+            pub fn consume(self) -> Result<()> { todo!() }
+        }
+    })
+    .unwrap_err();
+    assert_eq!(error, "methods must take `&self` or `&mut self`");
+}
+
+#[test]
+fn generic_impls_are_rejected() {
+    let error = methods(parse_quote!(
+        impl<T> Point<T> {}
+    ))
+    .unwrap_err();
+    assert_eq!(error, "foliose methods can't be generic");
+}
+
+#[test]
+fn two_functions_with_the_same_name_are_rejected() {
+    let error = methods(parse_quote! {
+        impl Point {
+            pub fn len(&self) -> Result<i32> { todo!() }
+            #[foliose(name = "len")]
+            pub fn size(&self) -> Result<i32> { todo!() }
+        }
+    })
+    .unwrap_err();
+    assert_eq!(error, "there is already a function called `len`");
+
+    let error = methods(parse_quote! {
+        impl Point {
+            pub fn new() -> Result<Point> { todo!() }
+            #[foliose(name = "new")]
+            pub fn origin() -> Result<Point> { todo!() }
+        }
+    })
+    .unwrap_err();
+    assert_eq!(error, "there is already a function called `new`");
+}
+
+#[test]
+fn a_method_and_a_static_function_can_share_a_name() {
+    methods(parse_quote! {
+        impl Point {
+            pub fn len(&self) -> Result<i32> { todo!() }
+            #[foliose(name = "len")]
+            pub fn count(points: Vec<i32>) -> Result<i32> { todo!() }
+        }
+    })
+    .unwrap();
+}
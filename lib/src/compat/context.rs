@@ -0,0 +1,111 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::script_fn::IntoArgs;
+use crate::{
+    error::{Error, Result},
+    gc::Tracker,
+    span::Span,
+    type_error,
+    value::{Call, Value},
+};
+
+/// The engine a native function is called from, as seen through its [`Context`].
+pub trait Host {
+    /// Gets a module the same way `import` does, with the same permissions.
+    fn import(&self, path: &str, span: Span) -> Result<Value>;
+    /// Reports something that is probably a mistake, without stopping the script.
+    fn warn(&self, message: String, span: Span);
+    /// The maps the engine's cycle collector starts from.
+    fn tracker(&self) -> &Tracker;
+    /// Counts a step against the engine's step and time limits, and stops if it has been
+    /// interrupted.
+    fn step(&self, span: Span) -> Result<()>;
+    /// Checks that a collection with `len` items is within the engine's size limit.
+    fn check_size(&self, len: usize, span: Span) -> Result<()>;
+}
+
+/// Where a native function was called from. Functions that take `&Context` as their first
+/// parameter are given one on each call, and aren't counted as taking it as an argument.
+///
+/// Errors from these functions keep the span they were made with, so they should be made
+/// with [`Context::span`] or [`Context::arg_span`].
+pub struct Context<'a> {
+    host: Option<&'a dyn Host>,
+    span: Span,
+    args: Vec<Span>,
+}
+
+impl<'a> Context<'a> {
+    /// Functions called without a host, like from [`Call::call`](crate::value::Call::call),
+    /// can't import modules, and their warnings are dropped.
+    pub fn new(host: Option<&'a dyn Host>, span: Span, args: Vec<Span>) -> Self {
+        Self { host, span, args }
+    }
+
+    /// The span of the whole call.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    /// The span of the argument at `index`, or of the call if it was left out.
+    pub fn arg_span(&self, index: usize) -> Span {
+        self.args.get(index).copied().unwrap_or(self.span)
+    }
+
+    /// Calls a function from the script, from the same engine.
+    pub fn call(&self, function: &Value, args: impl IntoArgs) -> Result<Value> {
+        let Value::Function(function) = function else {
+            return Err(Error::new(
+                type_error!("function", function.type_of()),
+                self.span,
+            ));
+        };
+        self.call_function(function, args)
+    }
+    /// Calls a function that is already known to be one, from the same engine.
+    pub fn call_function(&self, function: &Rc<dyn Call>, args: impl IntoArgs) -> Result<Value> {
+        let args = args.into_args(self.span);
+        match self.host {
+            Some(host) => function.call_from(host, args, Vec::new(), self.span),
+            None => function.call(args, self.span),
+        }
+    }
+
+    /// Imports a module, as the script would with `import(path)`.
+    pub fn import(&self, path: &str) -> Result<Value> {
+        match self.host {
+            Some(host) => host.import(path, self.span),
+            None => Err(Error::new(
+                format!("The module '{path}' can't be imported from here."),
+                self.span,
+            )),
+        }
+    }
+
+    /// The engine's cycle collector, which maps made by the function should be
+    /// [tracked](crate::gc::track) with if scripts can make cycles through them.
+    pub fn tracker(&self) -> Option<&'a Tracker> {
+        self.host.map(Host::tracker)
+    }
+
+    /// Counts a step of work against the engine's limits. Functions that loop for as long
+    /// as the script asks should call this on each round.
+    pub fn step(&self) -> Result<()> {
+        match self.host {
+            Some(host) => host.step(self.span),
+            None => Ok(()),
+        }
+    }
+    /// Checks that a collection the function is building can hold `len` items.
+    pub fn check_size(&self, len: usize) -> Result<()> {
+        match self.host {
+            Some(host) => host.check_size(len, self.span),
+            None => Ok(()),
+        }
+    }
+
+    pub fn warn(&self, message: impl Display, span: Span) {
+        if let Some(host) = self.host {
+            host.warn(message.to_string(), span);
+        }
+    }
+}
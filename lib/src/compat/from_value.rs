@@ -0,0 +1,229 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use variadics_please::all_tuples;
+
+use super::{type_name::TypeName, user_data::UserData};
+use crate::{
+    error::{Error, Result},
+    span::Span,
+    type_error,
+    value::{Call, MapRef, Value},
+};
+
+/// Fallible conversion out of a [`Value`], used for the arguments of native functions.
+/// `span` is the span of the value being converted.
+///
+/// Arguments are converted into owned values. A native function can instead borrow its
+/// first argument as a `&str` or `&Value`, through [`FromValueRef`].
+pub trait FromValue: TypeName + Sized {
+    fn from_value(value: Value, span: Span) -> Result<Self>;
+
+    /// The value used when the argument is left out, for types that can be. Only the last
+    /// arguments of a function can be left out.
+    fn omitted() -> Option<Self> {
+        None
+    }
+}
+
+/// Conversion of a borrowed [`Value`], used for the first argument of native functions
+/// that take `&str` or `&Value`, which is borrowed for the length of the call.
+pub trait FromValueRef: TypeName {
+    fn from_value_ref(value: &Value, span: Span) -> Result<&Self>;
+}
+
+fn mismatch<T: TypeName + ?Sized>(value: &Value, span: Span) -> Error {
+    Error::new(type_error!(T::type_name(), value.type_of()), span)
+}
+
+impl FromValueRef for Value {
+    fn from_value_ref(value: &Value, _: Span) -> Result<&Self> {
+        Ok(value)
+    }
+}
+impl FromValueRef for str {
+    fn from_value_ref(value: &Value, span: Span) -> Result<&Self> {
+        match value {
+            Value::String(string) => Ok(string),
+            v => Err(mismatch::<Self>(v, span)),
+        }
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value, _: Span) -> Result<Self> {
+        Ok(value)
+    }
+}
+impl FromValue for String {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::String(string) => Ok(string),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+impl FromValue for i32 {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Int(int) => Ok(int),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+impl FromValue for i64 {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        i32::from_value(value, span).map(Self::from)
+    }
+}
+impl FromValue for u32 {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        let int = i32::from_value(value, span)?;
+        Self::try_from(int).map_err(|_| Error::new(type_error!("a non-negative int", int), span))
+    }
+}
+impl FromValue for usize {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        let int = i32::from_value(value, span)?;
+        Self::try_from(int).map_err(|_| Error::new(type_error!("a non-negative int", int), span))
+    }
+}
+impl FromValue for f32 {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        let float = f64::from_value(value, span)?;
+        if float.is_finite() && float.abs() > f32::MAX.into() {
+            return Err(Error::new(type_error!("a 32-bit float", float), span));
+        }
+        Ok(float as f32)
+    }
+}
+impl FromValue for f64 {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Float(float) => Ok(float),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+impl FromValue for bool {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Boolean(bool) => Ok(bool),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+impl FromValue for () {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Null => Ok(()),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| T::from_value(item, span))
+                .collect(),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value, span).map(Some),
+        }
+    }
+    fn omitted() -> Option<Self> {
+        Some(None)
+    }
+}
+
+/// Converts the entries of a map, leaving out hidden ones like `__type`.
+fn entries<T: FromValue, M: FromIterator<(String, T)> + TypeName>(
+    value: Value,
+    span: Span,
+) -> Result<M> {
+    let convert = |map: &HashMap<String, Value>| {
+        map.iter()
+            .filter(|(key, _)| !key.starts_with("__"))
+            .map(|(key, value)| Ok((key.clone(), T::from_value(value.clone(), span)?)))
+            .collect()
+    };
+    match &value {
+        Value::Map(map) => convert(map),
+        Value::MapRef(map) => match map.borrow().as_hashmap() {
+            Some(map) => convert(map),
+            None => Err(mismatch::<M>(&value, span)),
+        },
+        v => Err(mismatch::<M>(v, span)),
+    }
+}
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        entries(value, span)
+    }
+}
+impl<T: FromValue> FromValue for BTreeMap<String, T> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        entries(value, span)
+    }
+}
+
+macro_rules! impl_tuple {
+    ($(($generic: ident, $var: ident)),*) => {
+        impl<$($generic: FromValue),*> FromValue for ($($generic,)*) {
+            fn from_value(value: Value, span: Span) -> Result<Self> {
+                let len = [$(stringify!($generic)),*].len();
+                match value {
+                    Value::Array(items) if items.len() == len => {
+                        let mut items = items.into_iter();
+                        $(let $var = $generic::from_value(items.next().unwrap(), span)?;)*
+                        Ok(($($var,)*))
+                    }
+                    v => Err(mismatch::<Self>(&v, span)),
+                }
+            }
+        }
+    };
+}
+
+all_tuples!(impl_tuple, 1, 16, A, a);
+
+impl FromValue for Rc<dyn Call> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Function(function) => Ok(function),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+/// Shared maps are passed as they are, so changes made to them are seen by the script.
+/// Owned maps are copied into a new shared map, so a native function that changes one
+/// only changes its own copy; functions that need to change the script's map should
+/// return the new map instead.
+impl FromValue for Rc<RefCell<dyn MapRef>> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::MapRef(map) => Ok(map),
+            Value::Map(map) => Ok(Rc::new(RefCell::new(map))),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
+impl FromValue for Rc<UserData> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::UserData(data) => Ok(data),
+            v => Err(mismatch::<Self>(&v, span)),
+        }
+    }
+}
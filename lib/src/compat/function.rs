@@ -1,19 +1,138 @@
 use std::{marker::PhantomData, rc::Rc};
 use variadics_please::all_tuples;
 
-use super::type_name::TypeName;
+use super::{
+    context::{Context, Host},
+    from_value::{FromValue, FromValueRef},
+    type_name::TypeName,
+    user_data::UserType,
+};
 use crate::{
     error::{Error, Result},
-    span::Span,
-    value::{Call, Value},
+    span::{Chunk, Span},
+    type_error,
+    value::{Call, Value, bind_named},
 };
 
+/// The type names of a function's parameters and return value, as given by [`TypeName`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub parameters: Vec<String>,
+    pub returns: String,
+}
+
+impl Signature {
+    /// How many arguments must be given. Trailing parameters whose type is optional, like
+    /// `int?`, can be left out.
+    pub fn required(&self) -> usize {
+        required(
+            &self
+                .parameters
+                .iter()
+                .map(|ty| ty.ends_with('?'))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
 pub struct RsFunction<Fn, Marker> {
     function: Fn,
     parameters: Vec<String>,
+    /// How many parameters come before the trailing ones that can be left out.
+    required: usize,
+    returns: String,
+    names: Vec<&'static str>,
     phantom: PhantomData<Marker>,
 }
 
+/// Marks a function that takes a [`Context`] as its first parameter.
+pub struct WithContext;
+/// Marks a function that borrows a [user data](super::user_data::UserData) value of type
+/// `S` as its first parameter.
+pub struct Ref<S>(PhantomData<S>);
+/// Marks a function that borrows a [user data](super::user_data::UserData) value of type
+/// `S` mutably as its first parameter.
+pub struct Mut<S>(PhantomData<S>);
+/// Marks a function that borrows its first argument as a `&B`, like `&str` or `&Value`.
+pub struct Borrowed<B: ?Sized>(PhantomData<B>);
+
+impl<Fn, Marker> RsFunction<Fn, Marker> {
+    /// Declares the parameter names, allowing the function to be called with named arguments.
+    pub fn with_names(mut self, names: &[&'static str]) -> Self {
+        debug_assert_eq!(names.len(), self.parameters.len());
+        self.names = names.to_vec();
+        self
+    }
+
+    fn check_arity(&self, given: usize, span: Span) -> Result<()> {
+        let len = self.parameters.len();
+        if (self.required..=len).contains(&given) {
+            return Ok(());
+        }
+        let expected = if self.required == len {
+            len.to_string()
+        } else {
+            format!("{} to {len}", self.required)
+        };
+        Err(Error::new(
+            format!(
+                "This function expects {expected} argument{}, but got {given}.",
+                if len == 1 { "" } else { "s" },
+            ),
+            span,
+        ))
+    }
+}
+
+/// Gives errors made without a span the span of the call.
+fn at_call(error: Error, span: Span) -> Error {
+    if error.span() == Span::default() {
+        error.with_span(span)
+    } else {
+        error
+    }
+}
+
+/// What native functions can return: anything that converts into a [`Value`], and ints
+/// wider than an `i32`, which fail if they don't fit in one.
+pub trait IntoValue {
+    fn into_value(self) -> Result<Value>;
+}
+
+impl<T: Into<Value>> IntoValue for T {
+    fn into_value(self) -> Result<Value> {
+        Ok(self.into())
+    }
+}
+
+macro_rules! impl_wide_into_value {
+    ($($type: ty),*) => {
+        $(
+            impl IntoValue for $type {
+                fn into_value(self) -> Result<Value> {
+                    Value::try_from(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_wide_into_value!(i64, u32, usize);
+
+/// Converts what a native function returned. Values that don't fit are reported at the
+/// call.
+fn returned<R: IntoValue>(value: R, span: Span) -> Result<Value> {
+    value.into_value().map_err(|e| at_call(e, span))
+}
+
+/// The number of parameters up to and including the last one that can't be left out.
+fn required(optional: &[bool]) -> usize {
+    optional
+        .iter()
+        .rposition(|optional| !optional)
+        .map_or(0, |i| i + 1)
+}
+
 pub trait IntoCallable<Marker>: Sized {
     fn into_callable(self) -> RsFunction<Self, Marker>;
 }
@@ -22,28 +141,46 @@ macro_rules! impl_fn {
     ($(($generic: ident, $var: ident)),*) => {
         impl<
             T: Fn($($generic),*) -> Result<R>,
-            R: TypeName + Into<Value>,
-            $($generic: TypeName + From<Value>),*
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
         > Call for RsFunction<T, (R, $($generic),*)> {
             #[allow(unused)]
-            fn call(&self, args: Vec<Value>, span: Span) -> Result<Value> {
+            fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+                self.check_arity(args.len(), span)?;
                 let mut iter = args.into_iter();
                 $(
-                    let $var: $generic = iter.next().ok_or_else(|| Error::new("This function expected more arguments.", span))?.into();
+                    let $var = match iter.next() {
+                        Some(arg) => $generic::from_value(arg.data, arg.span)?,
+                        None => $generic::omitted().unwrap(),
+                    };
                 )*
-                (self.function)($($var),*).map(|r| r.into()).map_err(|e| e.with_span(span))
+                (self.function)($($var),*).and_then(|r| returned(r, span)).map_err(|e| at_call(e, span))
+            }
+
+            fn parameter_names(&self) -> Option<Vec<&str>> {
+                (!self.names.is_empty()).then(|| self.names.clone())
+            }
+
+            fn signature(&self) -> Option<Signature> {
+                Some(Signature {
+                    parameters: self.parameters.clone(),
+                    returns: self.returns.clone(),
+                })
             }
         }
 
         impl<
             T: Fn($($generic),*) -> Result<R>,
-            R: TypeName + Into<Value>,
-            $($generic: TypeName + From<Value>),*
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
         > IntoCallable<(R, $($generic),*)> for T {
             fn into_callable(self) -> RsFunction<T, (R, $($generic),*)> {
                 RsFunction {
                     function: self,
                     parameters: vec![$($generic::type_name()),*],
+                    required: required(&[$($generic::omitted().is_some()),*]),
+                    returns: R::type_name(),
+                    names: Vec::new(),
                     phantom: PhantomData,
                 }
             }
@@ -52,14 +189,261 @@ macro_rules! impl_fn {
 
         impl<
             T: Fn($($generic),*) -> Result<R> + 'static,
-            R: TypeName + Into<Value> + 'static,
-            $($generic: TypeName + From<Value> + 'static),*
-        > Into<Value> for RsFunction<T, (R, $($generic),*)> {
-            fn into(self) -> Value {
-                Value::Function(Rc::new(self))
+            R: TypeName + IntoValue + 'static,
+            $($generic: FromValue + 'static),*
+        > From<RsFunction<T, (R, $($generic),*)>> for Value {
+            fn from(function: RsFunction<T, (R, $($generic),*)>) -> Self {
+                Value::Function(Rc::new(function))
             }
         }
     };
 }
 
 all_tuples!(impl_fn, 0, 16, A, a);
+
+/// Implements [`Call`] for functions whose first parameter is `&Context`, which is made
+/// for each call rather than taken from the arguments.
+macro_rules! impl_context_fn {
+    ($(($generic: ident, $var: ident)),*) => {
+        impl<
+            T: Fn(&Context, $($generic),*) -> Result<R>,
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
+        > RsFunction<T, (R, WithContext, $($generic),*)> {
+            fn call_with(
+                &self,
+                host: Option<&dyn Host>,
+                args: Vec<Chunk<Value>>,
+                span: Span,
+            ) -> Result<Value> {
+                self.check_arity(args.len(), span)?;
+                let context = Context::new(host, span, args.iter().map(|arg| arg.span).collect());
+                #[allow(unused_mut, unused_variables)]
+                let mut iter = args.into_iter();
+                $(
+                    let $var = match iter.next() {
+                        Some(arg) => $generic::from_value(arg.data, arg.span)?,
+                        None => $generic::omitted().unwrap(),
+                    };
+                )*
+                (self.function)(&context, $($var),*).and_then(|r| returned(r, span))
+            }
+        }
+
+        impl<
+            T: Fn(&Context, $($generic),*) -> Result<R>,
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
+        > Call for RsFunction<T, (R, WithContext, $($generic),*)> {
+            fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+                self.call_with(None, args, span)
+            }
+
+            fn call_from(
+                &self,
+                host: &dyn Host,
+                args: Vec<Chunk<Value>>,
+                named: Vec<(Chunk<&str>, Chunk<Value>)>,
+                span: Span,
+            ) -> Result<Value> {
+                let args = bind_named(self.parameter_names(), args, named, span)?;
+                self.call_with(Some(host), args, span)
+            }
+
+            fn parameter_names(&self) -> Option<Vec<&str>> {
+                (!self.names.is_empty()).then(|| self.names.clone())
+            }
+
+            fn signature(&self) -> Option<Signature> {
+                Some(Signature {
+                    parameters: self.parameters.clone(),
+                    returns: self.returns.clone(),
+                })
+            }
+        }
+
+        impl<
+            T: Fn(&Context, $($generic),*) -> Result<R>,
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
+        > IntoCallable<(R, WithContext, $($generic),*)> for T {
+            fn into_callable(self) -> RsFunction<T, (R, WithContext, $($generic),*)> {
+                RsFunction {
+                    function: self,
+                    parameters: vec![$($generic::type_name()),*],
+                    required: required(&[$($generic::omitted().is_some()),*]),
+                    returns: R::type_name(),
+                    names: Vec::new(),
+                    phantom: PhantomData,
+                }
+            }
+        }
+
+        impl<
+            T: Fn(&Context, $($generic),*) -> Result<R> + 'static,
+            R: TypeName + IntoValue + 'static,
+            $($generic: FromValue + 'static),*
+        > From<RsFunction<T, (R, WithContext, $($generic),*)>> for Value {
+            fn from(function: RsFunction<T, (R, WithContext, $($generic),*)>) -> Self {
+                Value::Function(Rc::new(function))
+            }
+        }
+    };
+}
+
+all_tuples!(impl_context_fn, 0, 15, A, a);
+
+/// Implements [`Call`] for functions whose first parameter is `&S` or `&mut S`, which is
+/// borrowed from a [user data](super::user_data::UserData) argument for the length of the
+/// call.
+macro_rules! impl_method {
+    ($marker: ident, $borrow: ident, [$($mutability: tt)?], $(($generic: ident, $var: ident)),*) => {
+        impl<
+            T: Fn(&$($mutability)? S, $($generic),*) -> Result<R>,
+            S: UserType,
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
+        > Call for RsFunction<T, (R, $marker<S>, $($generic),*)> {
+            fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+                self.check_arity(args.len(), span)?;
+                let mut iter = args.into_iter();
+                let this = iter.next().unwrap();
+                let data = match this.data {
+                    Value::UserData(data) => data,
+                    v => return Err(Error::new(type_error!(S::type_name(), v.type_of()), this.span)),
+                };
+                #[allow(unused_mut)]
+                let mut this = data.$borrow::<S>(this.span)?;
+                $(
+                    let $var = match iter.next() {
+                        Some(arg) => $generic::from_value(arg.data, arg.span)?,
+                        None => $generic::omitted().unwrap(),
+                    };
+                )*
+                (self.function)(&$($mutability)? this, $($var),*)
+                    .and_then(|r| returned(r, span))
+                    .map_err(|e| at_call(e, span))
+            }
+
+            fn parameter_names(&self) -> Option<Vec<&str>> {
+                (!self.names.is_empty()).then(|| self.names.clone())
+            }
+
+            fn signature(&self) -> Option<Signature> {
+                Some(Signature {
+                    parameters: self.parameters.clone(),
+                    returns: self.returns.clone(),
+                })
+            }
+        }
+
+        impl<
+            T: Fn(&$($mutability)? S, $($generic),*) -> Result<R>,
+            S: UserType,
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
+        > IntoCallable<(R, $marker<S>, $($generic),*)> for T {
+            fn into_callable(self) -> RsFunction<T, (R, $marker<S>, $($generic),*)> {
+                RsFunction {
+                    function: self,
+                    parameters: vec![S::type_name(), $($generic::type_name()),*],
+                    required: required(&[false, $($generic::omitted().is_some()),*]),
+                    returns: R::type_name(),
+                    names: Vec::new(),
+                    phantom: PhantomData,
+                }
+            }
+        }
+
+        impl<
+            T: Fn(&$($mutability)? S, $($generic),*) -> Result<R> + 'static,
+            S: UserType,
+            R: TypeName + IntoValue + 'static,
+            $($generic: FromValue + 'static),*
+        > From<RsFunction<T, (R, $marker<S>, $($generic),*)>> for Value {
+            fn from(function: RsFunction<T, (R, $marker<S>, $($generic),*)>) -> Self {
+                Value::Function(Rc::new(function))
+            }
+        }
+    };
+}
+
+macro_rules! impl_methods {
+    ($(($generic: ident, $var: ident)),*) => {
+        impl_method!(Ref, borrow, [], $(($generic, $var)),*);
+        impl_method!(Mut, borrow_mut, [mut], $(($generic, $var)),*);
+    };
+}
+
+all_tuples!(impl_methods, 0, 15, A, a);
+
+/// Implements [`Call`] for functions whose first parameter is borrowed from the first
+/// argument through [`FromValueRef`].
+macro_rules! impl_borrowed_fn {
+    ($(($generic: ident, $var: ident)),*) => {
+        impl<
+            T: Fn(&B, $($generic),*) -> Result<R>,
+            B: FromValueRef + ?Sized + 'static,
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
+        > Call for RsFunction<T, (R, Borrowed<B>, $($generic),*)> {
+            fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+                self.check_arity(args.len(), span)?;
+                let mut iter = args.into_iter();
+                let first = iter.next().unwrap();
+                let first = B::from_value_ref(&first.data, first.span)?;
+                $(
+                    let $var = match iter.next() {
+                        Some(arg) => $generic::from_value(arg.data, arg.span)?,
+                        None => $generic::omitted().unwrap(),
+                    };
+                )*
+                (self.function)(first, $($var),*)
+                    .and_then(|r| returned(r, span))
+                    .map_err(|e| at_call(e, span))
+            }
+
+            fn parameter_names(&self) -> Option<Vec<&str>> {
+                (!self.names.is_empty()).then(|| self.names.clone())
+            }
+
+            fn signature(&self) -> Option<Signature> {
+                Some(Signature {
+                    parameters: self.parameters.clone(),
+                    returns: self.returns.clone(),
+                })
+            }
+        }
+
+        impl<
+            T: Fn(&B, $($generic),*) -> Result<R>,
+            B: FromValueRef + ?Sized + 'static,
+            R: TypeName + IntoValue,
+            $($generic: FromValue),*
+        > IntoCallable<(R, Borrowed<B>, $($generic),*)> for T {
+            fn into_callable(self) -> RsFunction<T, (R, Borrowed<B>, $($generic),*)> {
+                RsFunction {
+                    function: self,
+                    parameters: vec![B::type_name(), $($generic::type_name()),*],
+                    required: required(&[false, $($generic::omitted().is_some()),*]),
+                    returns: R::type_name(),
+                    names: Vec::new(),
+                    phantom: PhantomData,
+                }
+            }
+        }
+
+        impl<
+            T: Fn(&B, $($generic),*) -> Result<R> + 'static,
+            B: FromValueRef + ?Sized + 'static,
+            R: TypeName + IntoValue + 'static,
+            $($generic: FromValue + 'static),*
+        > From<RsFunction<T, (R, Borrowed<B>, $($generic),*)>> for Value {
+            fn from(function: RsFunction<T, (R, Borrowed<B>, $($generic),*)>) -> Self {
+                Value::Function(Rc::new(function))
+            }
+        }
+    };
+}
+
+all_tuples!(impl_borrowed_fn, 0, 15, A, a);
@@ -1,5 +1,11 @@
+pub mod context;
+pub mod from_value;
 pub mod function;
+pub mod script_fn;
+#[cfg(test)]
+mod tests;
 pub mod type_name;
+pub mod user_data;
 
 #[doc(hidden)]
 pub use stringify_ident::stringify_ident;
@@ -40,7 +46,13 @@ macro_rules! interface {
                     _ => None,
                 }
             }
-            fn set(&mut self, _name: std::string::String, _value: $crate::value::Value) {}
+            fn set(
+                &mut self,
+                _name: std::string::String,
+                _value: $crate::value::Value,
+            ) -> $crate::error::Result<()> {
+                Ok(())
+            }
         }
     };
 }
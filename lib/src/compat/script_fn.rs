@@ -0,0 +1,124 @@
+use std::{marker::PhantomData, rc::Rc};
+use variadics_please::all_tuples;
+
+use super::{context::Context, from_value::FromValue, type_name::TypeName};
+use crate::{
+    error::{Error, Result},
+    span::{Chunk, Span},
+    type_error,
+    value::{Call, Value},
+};
+
+/// A tuple of values that can be passed to a [`ScriptFn`].
+pub trait IntoArgs {
+    fn len() -> usize;
+    fn into_args(self, span: Span) -> Vec<Chunk<Value>>;
+}
+
+macro_rules! impl_args {
+    ($(($generic: ident, $var: ident)),*) => {
+        impl<$($generic: Into<Value>),*> IntoArgs for ($($generic,)*) {
+            fn len() -> usize {
+                let names: &[&str] = &[$(stringify!($generic)),*];
+                names.len()
+            }
+
+            #[allow(unused)]
+            fn into_args(self, span: Span) -> Vec<Chunk<Value>> {
+                let ($($var,)*) = self;
+                vec![$(Chunk::new($var.into(), span)),*]
+            }
+        }
+    };
+}
+
+all_tuples!(impl_args, 0, 16, A, a);
+
+/// A function from a script, called with the arguments `A` and returning an `R`. It can
+/// be taken as a parameter by native functions, to accept callbacks.
+pub struct ScriptFn<A, R> {
+    function: Rc<dyn Call>,
+    phantom: PhantomData<fn(A) -> R>,
+}
+
+impl<A: IntoArgs, R: FromValue> ScriptFn<A, R> {
+    /// Wraps a function. Native functions are checked to take the right number of
+    /// arguments here, while script functions check when they are called.
+    pub fn new(function: Rc<dyn Call>, span: Span) -> Result<Self> {
+        if let Some(signature) = function.signature() {
+            let (required, len) = (signature.required(), signature.parameters.len());
+            if !(required..=len).contains(&A::len()) {
+                let plural = if A::len() == 1 { "" } else { "s" };
+                let takes = if required == len {
+                    len.to_string()
+                } else {
+                    format!("{required} to {len}")
+                };
+                return Err(Error::new(
+                    format!(
+                        "Expected a function that takes {} argument{plural}, but this one takes {takes}.",
+                        A::len(),
+                    ),
+                    span,
+                ));
+            }
+        }
+        Ok(Self {
+            function,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Calls the function. Errors from inside it keep their own span, while a result of
+    /// the wrong type is reported at `span`.
+    ///
+    /// This has no engine to call from, so native functions it reaches can't import
+    /// modules, and their warnings are dropped. Use [`ScriptFn::call_in`] where there is a
+    /// [`Context`].
+    pub fn call(&self, args: A, span: Span) -> Result<R> {
+        let value = self.function.call(args.into_args(span), span)?;
+        R::from_value(value, span)
+    }
+    /// Calls the function from the engine `ctx` belongs to, at the span of `ctx`.
+    pub fn call_in(&self, ctx: &Context, args: A) -> Result<R> {
+        let value = ctx.call_function(&self.function, args)?;
+        R::from_value(value, ctx.span())
+    }
+
+    pub fn function(&self) -> &Rc<dyn Call> {
+        &self.function
+    }
+}
+
+impl<A, R> Clone for ScriptFn<A, R> {
+    fn clone(&self) -> Self {
+        Self {
+            function: self.function.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<A, R> TypeName for ScriptFn<A, R> {
+    fn type_name() -> String {
+        String::from("function")
+    }
+}
+
+impl<A: IntoArgs, R: FromValue> FromValue for ScriptFn<A, R> {
+    fn from_value(value: Value, span: Span) -> Result<Self> {
+        match value {
+            Value::Function(function) => Self::new(function, span),
+            v => Err(Error::new(
+                type_error!(Self::type_name(), v.type_of()),
+                span,
+            )),
+        }
+    }
+}
+
+impl<A, R> From<ScriptFn<A, R>> for Value {
+    fn from(function: ScriptFn<A, R>) -> Self {
+        Value::Function(function.function)
+    }
+}
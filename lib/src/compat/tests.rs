@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use super::{context::Context, from_value::FromValue, function::IntoCallable, type_name::TypeName};
+use crate::{
+    error::Result,
+    span::{Chunk, Pos, Span},
+    value::{Call, Value},
+};
+
+fn span(col: usize) -> Span {
+    let start = Pos { col, ln: 0 };
+    Span {
+        start,
+        end: Pos {
+            col: col + 1,
+            ln: 0,
+        },
+    }
+}
+
+/// Calls `function` with `args`, giving argument `i` a span starting at column `i + 1`.
+fn call(function: impl Into<Value>, args: Vec<Value>) -> Result<Value> {
+    let Value::Function(function) = function.into() else {
+        unreachable!()
+    };
+    let args = args
+        .into_iter()
+        .enumerate()
+        .map(|(i, arg)| Chunk::new(arg, span(i + 1)))
+        .collect();
+    function.call(args, span(0))
+}
+
+#[test]
+fn unsigned_ints_reject_negative_values() {
+    assert_eq!(u32::from_value(Value::Int(3), span(1)).unwrap(), 3);
+    assert_eq!(usize::from_value(Value::Int(0), span(1)).unwrap(), 0);
+    let error = u32::from_value(Value::Int(-1), span(1)).unwrap_err();
+    assert_eq!(error.span(), span(1));
+    assert!(usize::from_value(Value::Int(-1), span(1)).is_err());
+}
+
+#[test]
+fn f32_rejects_floats_out_of_its_range() {
+    assert_eq!(f32::from_value(Value::Float(1.5), span(1)).unwrap(), 1.5);
+    assert!(f32::from_value(Value::Float(f64::MAX), span(1)).is_err());
+    assert!(f32::from_value(Value::Float(-f64::MAX), span(1)).is_err());
+    // Infinity and NaN have 32-bit versions.
+    assert_eq!(
+        f32::from_value(Value::Float(f64::INFINITY), span(1)).unwrap(),
+        f32::INFINITY
+    );
+    assert!(
+        f32::from_value(Value::Float(f64::NAN), span(1))
+            .unwrap()
+            .is_nan()
+    );
+}
+
+#[test]
+fn wide_ints_that_dont_fit_fail_when_returned() {
+    let fits = || -> Result<i64> { Ok(5) };
+    assert_eq!(call(fits.into_callable(), vec![]).unwrap(), Value::Int(5));
+    let too_big = || -> Result<usize> { Ok(usize::MAX) };
+    let error = call(too_big.into_callable(), vec![]).unwrap_err();
+    assert_eq!(error.span(), span(0));
+}
+
+#[test]
+fn type_names_of_compound_types() {
+    assert_eq!(Option::<i32>::type_name(), "int?");
+    assert_eq!(Option::<Vec<String>>::type_name(), "array<string>?");
+    assert_eq!(<(i32, String)>::type_name(), "[int, string]");
+    assert_eq!(HashMap::<String, f64>::type_name(), "map<float>");
+}
+
+#[test]
+fn trailing_optional_arguments_can_be_left_out() {
+    let add = |a: i32, b: Option<i32>| -> Result<i32> { Ok(a + b.unwrap_or(1)) };
+    assert_eq!(
+        call(add.into_callable(), vec![Value::Int(2)]).unwrap(),
+        Value::Int(3)
+    );
+    assert_eq!(
+        call(add.into_callable(), vec![Value::Int(2), Value::Int(5)]).unwrap(),
+        Value::Int(7)
+    );
+    assert!(call(add.into_callable(), vec![]).is_err());
+}
+
+#[test]
+fn functions_can_borrow_a_str_first_argument() {
+    let repeat = |s: &str, times: usize| -> Result<String> { Ok(s.repeat(times)) };
+    let function = repeat.into_callable();
+    assert_eq!(function.signature().unwrap().parameters, ["string", "int"]);
+    assert_eq!(
+        call(function, vec![Value::from("ab"), Value::Int(2)]).unwrap(),
+        Value::from("abab")
+    );
+    let error = call(repeat.into_callable(), vec![Value::Int(1), Value::Int(2)]).unwrap_err();
+    assert_eq!(error.span(), span(1));
+}
+
+#[test]
+fn functions_can_borrow_a_value_first_argument() {
+    let is_null = |value: &Value| -> Result<bool> { Ok(matches!(value, Value::Null)) };
+    assert_eq!(
+        call(is_null.into_callable(), vec![Value::Null]).unwrap(),
+        Value::Boolean(true)
+    );
+}
+
+#[test]
+fn a_mismatched_argument_is_reported_at_the_argument() {
+    let add = |a: i32, b: i32| -> Result<i32> { Ok(a + b) };
+    let error = call(add.into_callable(), vec![Value::Int(1), Value::Float(2.0)]).unwrap_err();
+    assert_eq!(error.span(), span(2));
+    assert!(
+        format!("{error:?}").contains("Expected int, but instead found float"),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn functions_taking_a_context_report_mismatches_at_the_argument() {
+    let len = |_: &Context, s: String| -> Result<usize> { Ok(s.len()) };
+    let error = call(len.into_callable(), vec![Value::Int(1)]).unwrap_err();
+    assert_eq!(error.span(), span(1));
+}
+
+#[test]
+fn the_wrong_number_of_arguments_is_an_error_at_the_call() {
+    let negate = |a: i32| -> Result<i32> { Ok(-a) };
+    let error = call(negate.into_callable(), vec![Value::Int(1), Value::Int(2)]).unwrap_err();
+    assert_eq!(error.span(), span(0));
+    assert!(
+        format!("{error:?}").contains("This function expects 1 argument, but got 2."),
+        "{error:?}"
+    );
+    let error = call(negate.into_callable(), vec![]).unwrap_err();
+    assert_eq!(error.span(), span(0));
+}
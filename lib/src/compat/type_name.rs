@@ -1,6 +1,13 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
-use crate::value::{MapRef, Value};
+use variadics_please::all_tuples;
+
+use super::user_data::UserData;
+use crate::value::{Call, MapRef, Value};
 
 pub trait TypeName {
     fn type_name() -> String;
@@ -22,11 +29,18 @@ impl_type_name!(
     Value = "*",
 
     String = "string",
+    str = "string",
     i32 = "int",
+    i64 = "int",
+    u32 = "int",
+    usize = "int",
     f64 = "float",
+    f32 = "float",
     bool = "bool",
     () = "null",
-    Rc<RefCell<dyn MapRef>> = "map"
+    Rc<RefCell<dyn MapRef>> = "map",
+    Rc<dyn Call> = "function",
+    Rc<UserData> = "userdata"
 );
 
 impl<T: TypeName> TypeName for Vec<T> {
@@ -34,3 +48,38 @@ impl<T: TypeName> TypeName for Vec<T> {
         format!("array<{}>", T::type_name())
     }
 }
+impl<T: TypeName + ?Sized> TypeName for &T {
+    fn type_name() -> String {
+        T::type_name()
+    }
+}
+/// Optional values are written as `T?`, and can be left out when they are the last
+/// arguments of a native function.
+impl<T: TypeName> TypeName for Option<T> {
+    fn type_name() -> String {
+        format!("{}?", T::type_name())
+    }
+}
+impl<T: TypeName> TypeName for HashMap<String, T> {
+    fn type_name() -> String {
+        format!("map<{}>", T::type_name())
+    }
+}
+impl<T: TypeName> TypeName for BTreeMap<String, T> {
+    fn type_name() -> String {
+        format!("map<{}>", T::type_name())
+    }
+}
+
+/// Tuples are arrays with a fixed length, written as `[int, string]`.
+macro_rules! impl_tuple {
+    ($($generic: ident),*) => {
+        impl<$($generic: TypeName),*> TypeName for ($($generic,)*) {
+            fn type_name() -> String {
+                format!("[{}]", [$($generic::type_name()),*].join(", "))
+            }
+        }
+    };
+}
+
+all_tuples!(impl_tuple, 1, 16, A);
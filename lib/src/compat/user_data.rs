@@ -0,0 +1,242 @@
+use std::{
+    any::Any,
+    cell::{Ref, RefCell, RefMut},
+    collections::HashMap,
+    fmt::{self, Debug},
+    rc::Rc,
+};
+
+use super::{context::Host, function::Signature, type_name::TypeName};
+use crate::{
+    error::{Error, Result},
+    gc::Edge,
+    span::{Chunk, Span},
+    type_error,
+    value::{Call, Value},
+};
+
+/// The properties of a Rust type that scripts can read and write. Usually implemented
+/// with `#[derive(FolioseType)]`.
+pub trait Fields {
+    fn get_field(&self, name: &str) -> Option<Value>;
+    /// Sets a property, converting the value to its type. Returns `None` if there is no
+    /// property with that name.
+    fn set_field(&mut self, name: &str, value: Value, span: Span) -> Option<Result<()>>;
+}
+
+/// A Rust type that scripts can use as [`UserData`]. Usually implemented with
+/// `#[foliose_lib::methods]` on an impl block.
+pub trait UserType: TypeName + Fields + Sized + 'static {
+    /// Functions that take `self`, called on values of the type.
+    fn methods() -> Rc<HashMap<String, Value>> {
+        Rc::default()
+    }
+    /// The method called `name`. Values of the type look their methods up with this, so
+    /// implementing it saves building the whole table for each value.
+    fn method(name: &str) -> Option<Value> {
+        Self::methods().get(name).cloned()
+    }
+    /// Functions that don't take `self`, like constructors.
+    fn statics() -> HashMap<String, Value> {
+        HashMap::new()
+    }
+    /// The function that creates values of the type, if it has one.
+    fn constructor() -> Option<Value> {
+        None
+    }
+
+    /// A module holding the type's static functions, to be registered with an engine.
+    fn module() -> Value {
+        Value::Map(Self::statics())
+    }
+}
+
+impl<T: UserType> From<T> for Value {
+    fn from(data: T) -> Self {
+        Value::UserData(Rc::new(UserData::object(data)))
+    }
+}
+
+/// Reaches the [`Fields`] of user data without knowing its type.
+struct FieldAccess {
+    get: fn(&dyn Any, &str) -> Option<Value>,
+    set: fn(&mut dyn Any, &str, Value, Span) -> Option<Result<()>>,
+}
+
+impl FieldAccess {
+    fn new<T: Fields + 'static>() -> Self {
+        Self {
+            get: |data, name| data.downcast_ref::<T>()?.get_field(name),
+            set: |data, name, value, span| data.downcast_mut::<T>()?.set_field(name, value, span),
+        }
+    }
+}
+
+/// A Rust value that scripts can pass around but can't look into. Its type name comes
+/// from [`TypeName`], and it can have properties and a table of methods, which are
+/// called with the value itself as their first argument.
+///
+/// The [cycle collector](crate::gc) doesn't look inside user data, so values it holds on
+/// to are always kept alive.
+pub struct UserData {
+    type_name: String,
+    data: RefCell<Box<dyn Any>>,
+    fields: Option<FieldAccess>,
+    methods: Methods,
+}
+
+/// Where a value's methods are looked up.
+enum Methods {
+    None,
+    Table(Rc<HashMap<String, Value>>),
+    Lookup(fn(&str) -> Option<Value>),
+}
+
+impl UserData {
+    pub fn new<T: TypeName + 'static>(data: T) -> Self {
+        Self {
+            type_name: T::type_name(),
+            data: RefCell::new(Box::new(data)),
+            fields: None,
+            methods: Methods::None,
+        }
+    }
+    /// Wraps a value with the properties and methods of its [`UserType`].
+    pub fn object<T: UserType>(data: T) -> Self {
+        Self {
+            fields: Some(FieldAccess::new::<T>()),
+            methods: Methods::Lookup(T::method),
+            ..Self::new(data)
+        }
+    }
+    /// Gives the value methods. The table is usually built once for the type and shared
+    /// between all of its values.
+    pub fn with_methods(mut self, methods: Rc<HashMap<String, Value>>) -> Self {
+        self.methods = Methods::Table(methods);
+        self
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Borrows the value as a `T`. `span` is the span of the value, used if it is
+    /// something else or is already borrowed mutably.
+    pub fn borrow<T: TypeName + 'static>(&self, span: Span) -> Result<Ref<'_, T>> {
+        let data = self.data.try_borrow().map_err(|_| self.in_use(span))?;
+        Ref::filter_map(data, |data| data.downcast_ref::<T>())
+            .map_err(|_| Error::new(type_error!(T::type_name(), self.type_name), span))
+    }
+    /// Borrows the value mutably as a `T`.
+    pub fn borrow_mut<T: TypeName + 'static>(&self, span: Span) -> Result<RefMut<'_, T>> {
+        let data = self.data.try_borrow_mut().map_err(|_| self.in_use(span))?;
+        RefMut::filter_map(data, |data| data.downcast_mut::<T>())
+            .map_err(|_| Error::new(type_error!(T::type_name(), self.type_name), span))
+    }
+    fn in_use(&self, span: Span) -> Error {
+        Error::new(
+            format!("This {} is already being used.", self.type_name),
+            span,
+        )
+    }
+
+    /// Gets a property, or a method bound to `this`. `span` is the span of the value.
+    pub fn get(this: &Rc<Self>, name: &str, span: Span) -> Result<Option<Value>> {
+        if let Some(fields) = &this.fields {
+            let data = this.data.try_borrow().map_err(|_| this.in_use(span))?;
+            if let Some(value) = (fields.get)(data.as_ref(), name) {
+                return Ok(Some(value));
+            }
+        }
+        Ok(Self::method(this, name))
+    }
+    /// Sets a property. `span` is the span of the property being set.
+    pub fn set(&self, name: &str, value: Value, span: Span) -> Result<()> {
+        let no_property = || {
+            Error::new(
+                format!("The type {} has no property '{name}'.", self.type_name),
+                span,
+            )
+        };
+        let fields = self.fields.as_ref().ok_or_else(no_property)?;
+        let mut data = self.data.try_borrow_mut().map_err(|_| self.in_use(span))?;
+        (fields.set)(data.as_mut(), name, value, span).unwrap_or_else(|| Err(no_property()))
+    }
+
+    fn method(this: &Rc<Self>, name: &str) -> Option<Value> {
+        let function = match &this.methods {
+            Methods::None => return None,
+            Methods::Table(methods) => methods.get(name)?.clone(),
+            Methods::Lookup(method) => method(name)?,
+        };
+        let function = match function {
+            Value::Function(function) => function,
+            value => return Some(value),
+        };
+        Some(Value::Function(Rc::new(Method {
+            this: Value::UserData(this.clone()),
+            function,
+        })))
+    }
+}
+
+impl Debug for UserData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UserData").field(&self.type_name).finish()
+    }
+}
+
+/// A method taken from a value's method table, which passes the value on as the first
+/// argument.
+struct Method {
+    this: Value,
+    function: Rc<dyn Call>,
+}
+
+impl Call for Method {
+    fn call(&self, mut args: Vec<Chunk<Value>>, span: Span) -> Result<Value> {
+        args.insert(0, Chunk::new(self.this.clone(), span));
+        self.function.call(args, span)
+    }
+
+    fn parameter_names(&self) -> Option<Vec<&str>> {
+        let mut names = self.function.parameter_names()?;
+        if !names.is_empty() {
+            names.remove(0);
+        }
+        Some(names)
+    }
+
+    fn signature(&self) -> Option<Signature> {
+        let mut signature = self.function.signature()?;
+        if !signature.parameters.is_empty() {
+            signature.parameters.remove(0);
+        }
+        Some(signature)
+    }
+
+    fn call_named(
+        &self,
+        mut args: Vec<Chunk<Value>>,
+        named: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        args.insert(0, Chunk::new(self.this.clone(), span));
+        self.function.call_named(args, named, span)
+    }
+
+    fn call_from(
+        &self,
+        host: &dyn Host,
+        mut args: Vec<Chunk<Value>>,
+        named: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        args.insert(0, Chunk::new(self.this.clone(), span));
+        self.function.call_from(host, args, named, span)
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        visit(Edge::Function(&self.function));
+    }
+}
@@ -4,6 +4,32 @@ use crate::span::Span;
 pub struct Error {
     message: String,
     span: Span,
+    kind: ErrorKind,
+}
+
+/// What caused an error, for hosts that handle some errors differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorKind {
+    /// A mistake in the script, like a type error or a missing variable.
+    #[default]
+    Script,
+    /// The script went over one of the limits its engine was given.
+    LimitExceeded(Limit),
+    /// The script tried to use something its engine wasn't allowed to.
+    NotPermitted,
+    /// The host stopped the script while it was running.
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps,
+    Depth,
+    /// The native stack, which deep recursion can run out of before reaching the depth
+    /// limit.
+    Stack,
+    CollectionSize,
+    Time,
 }
 
 impl Error {
@@ -11,15 +37,30 @@ impl Error {
         Self {
             message: message.into(),
             span,
+            kind: ErrorKind::Script,
         }
     }
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
     pub fn with_span(mut self, span: Span) -> Self {
         self.span = span;
         self
     }
 
     pub fn display(&self, src: &str) {
-        println!("error: {}", self.message);
+        self.display_as("error", src);
+    }
+    /// Prints the message with a different label, such as `warning`.
+    pub fn display_as(&self, label: &str, src: &str) {
+        println!("{label}: {}", self.message);
         let lines = src
             .lines()
             .skip(self.span.start.ln)
@@ -58,6 +99,6 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[macro_export]
 macro_rules! type_error {
     ($expected: expr, $found: expr) => {
-        format!("Expected {}, but instead found {}", $expected, $found);
+        format!("Expected {}, but instead found {}", $expected, $found)
     };
 }
@@ -0,0 +1,209 @@
+//! Cycle collection. Values are reference counted, so a map that (directly or through a
+//! function's scope) refers back to itself is never freed. [`collect`] finds maps that are
+//! only kept alive by such cycles and clears them, which lets the cycle drop.
+//!
+//! Each engine keeps its own [`Tracker`], so collecting in one engine never looks at
+//! another's maps.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    compat::type_name::TypeName,
+    value::{Call, MapRef, Value},
+};
+
+/// The maps an engine has registered as possibly ending up in a cycle.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    maps: RefCell<Vec<Weak<RefCell<dyn MapRef>>>>,
+    /// How many tracked maps were alive after the last time dead ones were removed.
+    live: Cell<usize>,
+}
+
+/// A reference from one value to a shared map or function, visited by `trace` methods.
+pub enum Edge<'a> {
+    Map(&'a Rc<RefCell<dyn MapRef>>),
+    Function(&'a Rc<dyn Call>),
+}
+
+impl Edge<'_> {
+    fn handle(&self) -> Handle {
+        match self {
+            Self::Map(map) => Handle::Map((*map).clone()),
+            Self::Function(function) => Handle::Function((*function).clone()),
+        }
+    }
+}
+
+/// Registers a map that may end up in a cycle. Only maps reachable from tracked maps are
+/// collected.
+pub fn track<T: MapRef + 'static>(tracker: &Tracker, map: &Rc<RefCell<T>>) {
+    let mut tracked = tracker.maps.borrow_mut();
+    let map: Weak<RefCell<T>> = Rc::downgrade(map);
+    tracked.push(map);
+    if tracked.len() > tracker.live.get() * 2 + 64 {
+        tracked.retain(|map| map.strong_count() > 0);
+        tracker.live.set(tracked.len());
+    }
+}
+
+/// What a call to [`collect`] found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Tracked maps that were still alive.
+    pub tracked: usize,
+    /// Maps and functions reachable from the tracked maps.
+    pub visited: usize,
+    /// Maps that were only alive because of a cycle and were cleared. Functions in a cycle
+    /// are freed through the maps they refer to, and maps borrowed elsewhere are skipped.
+    pub collected: usize,
+}
+
+impl From<Stats> for Value {
+    fn from(stats: Stats) -> Self {
+        let int = |count: usize| Value::Int(i32::try_from(count).unwrap_or(i32::MAX));
+        Value::Map(HashMap::from([
+            (String::from("tracked"), int(stats.tracked)),
+            (String::from("visited"), int(stats.visited)),
+            (String::from("collected"), int(stats.collected)),
+        ]))
+    }
+}
+impl TypeName for Stats {
+    fn type_name() -> String {
+        String::from("map")
+    }
+}
+
+enum Handle {
+    Map(Rc<RefCell<dyn MapRef>>),
+    Function(Rc<dyn Call>),
+}
+
+impl Handle {
+    fn key(&self) -> *const () {
+        match self {
+            Self::Map(map) => Rc::as_ptr(map) as *const (),
+            Self::Function(function) => Rc::as_ptr(function) as *const (),
+        }
+    }
+    fn strong_count(&self) -> usize {
+        match self {
+            Self::Map(map) => Rc::strong_count(map),
+            Self::Function(function) => Rc::strong_count(function),
+        }
+    }
+}
+
+struct Node {
+    handle: Handle,
+    /// References to this node from other nodes.
+    internal: usize,
+    children: Vec<usize>,
+    live: bool,
+}
+
+#[derive(Default)]
+struct Heap {
+    nodes: Vec<Node>,
+    index: HashMap<*const (), usize>,
+}
+
+impl Heap {
+    fn node(&mut self, handle: Handle) -> usize {
+        let key = handle.key();
+        if let Some(&i) = self.index.get(&key) {
+            return i;
+        }
+        self.nodes.push(Node {
+            handle,
+            internal: 0,
+            children: Vec::new(),
+            live: false,
+        });
+        self.index.insert(key, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Finds every node reachable from the first ones, counting the references between them.
+    fn explore(&mut self) {
+        let mut next = 0;
+        while next < self.nodes.len() {
+            let mut edges = Vec::new();
+            let mut visit = |edge: Edge| edges.push(edge.handle());
+            match &self.nodes[next].handle {
+                // A map that is being changed can't be looked into. Anything it refers to
+                // seems to be referenced from outside, so is kept alive.
+                Handle::Map(map) => {
+                    if let Ok(map) = map.try_borrow() {
+                        map.trace(&mut visit)
+                    }
+                }
+                Handle::Function(function) => function.trace(&mut visit),
+            }
+            for handle in edges {
+                let child = self.node(handle);
+                self.nodes[child].internal += 1;
+                self.nodes[next].children.push(child);
+            }
+            next += 1;
+        }
+    }
+
+    /// Marks every node that is referenced from outside the heap, and everything they
+    /// refer to, as live.
+    fn mark(&mut self) {
+        let mut stack: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| {
+                let node = &self.nodes[i];
+                // One reference is the heap's own handle.
+                node.handle.strong_count() - 1 > node.internal
+            })
+            .collect();
+        while let Some(i) = stack.pop() {
+            if self.nodes[i].live {
+                continue;
+            }
+            self.nodes[i].live = true;
+            stack.extend(&self.nodes[i].children);
+        }
+    }
+}
+
+/// Clears every map that can only be reached through a reference cycle, starting from the
+/// maps in `tracker`.
+pub fn collect(tracker: &Tracker) -> Stats {
+    let tracked: Vec<_> = {
+        let mut tracked = tracker.maps.borrow_mut();
+        tracked.retain(|map| map.strong_count() > 0);
+        tracker.live.set(tracked.len());
+        tracked.iter().filter_map(Weak::upgrade).collect()
+    };
+    let mut heap = Heap::default();
+    for map in &tracked {
+        heap.node(Handle::Map(map.clone()));
+    }
+    let count = tracked.len();
+    drop(tracked);
+    heap.explore();
+    heap.mark();
+
+    let mut collected = 0;
+    for node in heap.nodes.iter().filter(|node| !node.live) {
+        if let Handle::Map(map) = &node.handle
+            && let Ok(mut map) = map.try_borrow_mut()
+        {
+            map.clear();
+            collected += 1;
+        }
+    }
+    Stats {
+        tracked: count,
+        visited: heap.nodes.len(),
+        collected,
+    }
+}
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use crate::span::Chunk;
 
 pub mod owned;
@@ -8,6 +10,8 @@ pub enum Instruction<'a> {
         map: Chunk<Reporter<'a>>,
         name: Chunk<&'a str>,
         value: Chunk<Reporter<'a>>,
+        /// The annotated type of a variable. Annotations are only used by the checker.
+        ty: Option<Chunk<&'a str>>,
     },
     While {
         condition: Chunk<Reporter<'a>>,
@@ -28,27 +32,41 @@ pub enum Reporter<'a> {
     Parent(usize),
 
     Null,
-    ConstStr(&'a str),
+    /// Owned when the string was built by constant folding.
+    ConstStr(Cow<'a, str>),
     ConstInt(i32),
     ConstFloat(f64),
     ConstBool(bool),
 
     Block(Vec<Chunk<Instruction<'a>>>),
     Array(Chunk<Vec<Chunk<Self>>>),
+    Object(Vec<Chunk<ObjectEntry<'a>>>),
     Function {
-        parameters: Vec<Chunk<&'a str>>,
+        parameters: Vec<Parameter<'a>>,
+        returns: Option<Chunk<&'a str>>,
         body: Chunk<Box<Self>>,
     },
+    /// Creates the constructor for a `type` declaration. Fields are pairs of names and
+    /// type names.
+    RecordType {
+        name: Chunk<&'a str>,
+        fields: Vec<(Chunk<&'a str>, Chunk<&'a str>)>,
+    },
 
+    /// `optional` gets evaluate to null instead of failing when the map is null or the
+    /// property is missing. A null map also makes the gets and calls made on the result
+    /// evaluate to null, so `x?.a.b` is null when `x` is.
     Get {
         map: Chunk<Box<Self>>,
         name: Chunk<&'a str>,
+        optional: bool,
     },
     DynGet {
         map: Chunk<Box<Self>>,
         attr: Chunk<Box<Self>>,
+        optional: bool,
     },
-    Call(Chunk<Box<Self>>, Vec<Chunk<Self>>),
+    Call(Chunk<Box<Self>>, Vec<Chunk<Argument<'a>>>),
 
     Import(Chunk<&'a str>),
     If {
@@ -80,6 +98,8 @@ pub enum Reporter<'a> {
         a: Chunk<Box<Self>>,
         b: Chunk<Box<Self>>,
     },
+    /// Logical operators only accept booleans, like `if` and `while` conditions. `b` is
+    /// only evaluated when `a` doesn't already decide the result.
     And {
         a: Chunk<Box<Self>>,
         b: Chunk<Box<Self>>,
@@ -88,6 +108,11 @@ pub enum Reporter<'a> {
         a: Chunk<Box<Self>>,
         b: Chunk<Box<Self>>,
     },
+    /// Evaluates to `b` only if `a` is null.
+    Coalesce {
+        a: Chunk<Box<Self>>,
+        b: Chunk<Box<Self>>,
+    },
     Equality {
         a: Chunk<Box<Self>>,
         b: Chunk<Box<Self>>,
@@ -101,8 +126,46 @@ pub enum Reporter<'a> {
     Negative(Chunk<Box<Self>>),
 }
 
+#[derive(Debug, Clone)]
+pub struct Parameter<'a> {
+    pub name: Chunk<&'a str>,
+    pub ty: Option<Chunk<&'a str>>,
+    pub default: Option<Chunk<Reporter<'a>>>,
+    /// Collects the remaining arguments into an array. Only the last parameter can be a rest parameter.
+    pub rest: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum ObjectEntry<'a> {
+    Field(Chunk<&'a str>, Chunk<Reporter<'a>>),
+    Computed(Chunk<Reporter<'a>>, Chunk<Reporter<'a>>),
+    /// Copies every entry of another map.
+    Spread(Reporter<'a>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Argument<'a> {
+    Positional(Reporter<'a>),
+    /// Expands an array into positional arguments.
+    Spread(Reporter<'a>),
+    Named(Chunk<&'a str>, Chunk<Reporter<'a>>),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Comparison {
     GreaterThan,
     LessThan,
+    GreaterOrEqual,
+    LessOrEqual,
+}
+
+impl Comparison {
+    pub fn compare<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Self::GreaterThan => a > b,
+            Self::LessThan => a < b,
+            Self::GreaterOrEqual => a >= b,
+            Self::LessOrEqual => a <= b,
+        }
+    }
 }
@@ -1,9 +1,9 @@
-use std::slice::Iter;
+use std::{borrow::Cow, slice::Iter};
 
 use crate::span::Chunk;
 use self_cell::self_cell;
 
-use super::{Instruction, Reporter};
+use super::{Argument, Instruction, ObjectEntry, Parameter, Reporter};
 
 type Dependent<'a> = Chunk<Reporter<'a>>;
 self_cell!(
@@ -20,10 +20,18 @@ pub struct OwnedReporter {
 
 fn visit_instruction(owned_buf: &mut Vec<String>, instruction: &Instruction) {
     match instruction {
-        Instruction::Set { map, name, value } => {
+        Instruction::Set {
+            map,
+            name,
+            value,
+            ty,
+        } => {
             visit_reporter(owned_buf, &map.data);
             owned_buf.push(name.data.to_string());
             visit_reporter(owned_buf, &value.data);
+            if let Some(ty) = ty {
+                owned_buf.push(ty.data.to_string());
+            }
         }
         Instruction::While { condition, body } => {
             visit_reporter(owned_buf, &condition.data);
@@ -60,24 +68,67 @@ fn visit_reporter(owned_buf: &mut Vec<String>, reporter: &Reporter) {
                 visit_reporter(owned_buf, &item.data);
             }
         }
-        Reporter::Function { parameters, body } => {
+        Reporter::Object(entries) => {
+            for entry in entries {
+                match &entry.data {
+                    ObjectEntry::Field(key, value) => {
+                        owned_buf.push(key.data.to_string());
+                        visit_reporter(owned_buf, &value.data);
+                    }
+                    ObjectEntry::Computed(key, value) => {
+                        visit_reporter(owned_buf, &key.data);
+                        visit_reporter(owned_buf, &value.data);
+                    }
+                    ObjectEntry::Spread(value) => visit_reporter(owned_buf, value),
+                }
+            }
+        }
+        Reporter::Function {
+            parameters,
+            returns,
+            body,
+        } => {
             for parameter in parameters {
-                owned_buf.push(parameter.data.to_string());
+                owned_buf.push(parameter.name.data.to_string());
+                if let Some(ty) = &parameter.ty {
+                    owned_buf.push(ty.data.to_string());
+                }
+                if let Some(default) = &parameter.default {
+                    visit_reporter(owned_buf, &default.data);
+                }
+            }
+            if let Some(returns) = returns {
+                owned_buf.push(returns.data.to_string());
             }
             visit_reporter(owned_buf, &body.data);
         }
-        Reporter::Get { map, name } => {
+        Reporter::RecordType { name, fields } => {
+            owned_buf.push(name.data.to_string());
+            for (field, ty) in fields {
+                owned_buf.push(field.data.to_string());
+                owned_buf.push(ty.data.to_string());
+            }
+        }
+        Reporter::Get { map, name, .. } => {
             visit_reporter(owned_buf, &map.data);
             owned_buf.push(name.data.to_string());
         }
-        Reporter::DynGet { map, attr } => {
+        Reporter::DynGet { map, attr, .. } => {
             visit_reporter(owned_buf, &map.data);
             visit_reporter(owned_buf, &attr.data);
         }
         Reporter::Call(func, args) => {
             visit_reporter(owned_buf, &func.data);
             for arg in args {
-                visit_reporter(owned_buf, &arg.data);
+                match &arg.data {
+                    Argument::Positional(value) | Argument::Spread(value) => {
+                        visit_reporter(owned_buf, value)
+                    }
+                    Argument::Named(name, value) => {
+                        owned_buf.push(name.data.to_string());
+                        visit_reporter(owned_buf, &value.data);
+                    }
+                }
             }
         }
         Reporter::If { blocks, else_block } => {
@@ -97,6 +148,7 @@ fn visit_reporter(owned_buf: &mut Vec<String>, reporter: &Reporter) {
         | Reporter::Concat { a, b }
         | Reporter::And { a, b }
         | Reporter::Or { a, b }
+        | Reporter::Coalesce { a, b }
         | Reporter::Equality { a, b }
         | Reporter::Inequality { a, b, op: _ } => {
             visit_reporter(owned_buf, &a.data);
@@ -112,10 +164,16 @@ fn build_instruction<'a, 'b>(
 ) -> Chunk<Instruction<'b>> {
     Chunk::new(
         match instruction.data {
-            Instruction::Set { map, name, value } => Instruction::Set {
+            Instruction::Set {
+                map,
+                name,
+                value,
+                ty,
+            } => Instruction::Set {
                 map: build_reporter(owned_buf, map),
                 name: Chunk::new(owned_buf.next().unwrap().as_str(), name.span),
                 value: build_reporter(owned_buf, value),
+                ty: ty.map(|ty| Chunk::new(owned_buf.next().unwrap().as_str(), ty.span)),
             },
             Instruction::While { condition, body } => Instruction::While {
                 condition: build_reporter(owned_buf, condition),
@@ -154,7 +212,9 @@ fn build_reporter<'a, 'b>(
         match reporter.data {
             Reporter::Parent(up) => Reporter::Parent(up),
             Reporter::Null => Reporter::Null,
-            Reporter::ConstStr(_) => Reporter::ConstStr(owned_buf.next().unwrap().as_str()),
+            Reporter::ConstStr(_) => {
+                Reporter::ConstStr(Cow::Borrowed(owned_buf.next().unwrap().as_str()))
+            }
             Reporter::ConstInt(int) => Reporter::ConstInt(int),
             Reporter::ConstFloat(float) => Reporter::ConstFloat(float),
             Reporter::ConstBool(bool) => Reporter::ConstBool(bool),
@@ -171,25 +231,65 @@ fn build_reporter<'a, 'b>(
                     .collect(),
                 items.span,
             )),
-            Reporter::Function { parameters, body } => Reporter::Function {
-                parameters: owned_buf
-                    .zip(parameters)
-                    .map(|(o, p)| Chunk::new(o.as_str(), p.span))
+            Reporter::Object(entries) => Reporter::Object(
+                entries
+                    .into_iter()
+                    .map(|entry| build_object_entry(owned_buf, entry))
                     .collect(),
+            ),
+            Reporter::Function {
+                parameters,
+                returns,
+                body,
+            } => Reporter::Function {
+                parameters: parameters
+                    .into_iter()
+                    .map(|p| Parameter {
+                        name: Chunk::new(owned_buf.next().unwrap().as_str(), p.name.span),
+                        ty: p
+                            .ty
+                            .map(|ty| Chunk::new(owned_buf.next().unwrap().as_str(), ty.span)),
+                        default: p.default.map(|d| build_reporter(owned_buf, d)),
+                        rest: p.rest,
+                    })
+                    .collect(),
+                returns: returns.map(|ty| Chunk::new(owned_buf.next().unwrap().as_str(), ty.span)),
                 body: build_reporter(owned_buf, body.unbox()).as_box(),
             },
-            Reporter::Get { map, name } => Reporter::Get {
+            Reporter::RecordType { name, fields } => Reporter::RecordType {
+                name: Chunk::new(owned_buf.next().unwrap().as_str(), name.span),
+                fields: fields
+                    .into_iter()
+                    .map(|(field, ty)| {
+                        (
+                            Chunk::new(owned_buf.next().unwrap().as_str(), field.span),
+                            Chunk::new(owned_buf.next().unwrap().as_str(), ty.span),
+                        )
+                    })
+                    .collect(),
+            },
+            Reporter::Get {
+                map,
+                name,
+                optional,
+            } => Reporter::Get {
                 map: build_reporter(owned_buf, map.unbox()).as_box(),
                 name: Chunk::new(owned_buf.next().unwrap(), name.span),
+                optional,
             },
-            Reporter::DynGet { map, attr } => Reporter::DynGet {
+            Reporter::DynGet {
+                map,
+                attr,
+                optional,
+            } => Reporter::DynGet {
                 map: build_reporter(owned_buf, map.unbox()).as_box(),
                 attr: build_reporter(owned_buf, attr.unbox()).as_box(),
+                optional,
             },
             Reporter::Call(func, args) => Reporter::Call(
                 build_reporter(owned_buf, func.unbox()).as_box(),
                 args.into_iter()
-                    .map(|arg| build_reporter(owned_buf, arg))
+                    .map(|arg| build_argument(owned_buf, arg))
                     .collect(),
             ),
             Reporter::If { blocks, else_block } => Reporter::If {
@@ -236,6 +336,10 @@ fn build_reporter<'a, 'b>(
                 a: build_reporter(owned_buf, a.unbox()).as_box(),
                 b: build_reporter(owned_buf, b.unbox()).as_box(),
             },
+            Reporter::Coalesce { a, b } => Reporter::Coalesce {
+                a: build_reporter(owned_buf, a.unbox()).as_box(),
+                b: build_reporter(owned_buf, b.unbox()).as_box(),
+            },
             Reporter::Equality { a, b } => Reporter::Equality {
                 a: build_reporter(owned_buf, a.unbox()).as_box(),
                 b: build_reporter(owned_buf, b.unbox()).as_box(),
@@ -258,6 +362,43 @@ fn build_reporter<'a, 'b>(
         reporter.span,
     )
 }
+fn build_object_entry<'a, 'b>(
+    owned_buf: &mut Iter<'b, String>,
+    entry: Chunk<ObjectEntry<'a>>,
+) -> Chunk<ObjectEntry<'b>> {
+    let span = entry.span;
+    entry.map(|entry| match entry {
+        ObjectEntry::Field(key, value) => ObjectEntry::Field(
+            Chunk::new(owned_buf.next().unwrap().as_str(), key.span),
+            build_reporter(owned_buf, value),
+        ),
+        ObjectEntry::Computed(key, value) => ObjectEntry::Computed(
+            build_reporter(owned_buf, key),
+            build_reporter(owned_buf, value),
+        ),
+        ObjectEntry::Spread(value) => {
+            ObjectEntry::Spread(build_reporter(owned_buf, Chunk::new(value, span)).data)
+        }
+    })
+}
+fn build_argument<'a, 'b>(
+    owned_buf: &mut Iter<'b, String>,
+    argument: Chunk<Argument<'a>>,
+) -> Chunk<Argument<'b>> {
+    let span = argument.span;
+    argument.map(|argument| match argument {
+        Argument::Positional(value) => {
+            Argument::Positional(build_reporter(owned_buf, Chunk::new(value, span)).data)
+        }
+        Argument::Spread(value) => {
+            Argument::Spread(build_reporter(owned_buf, Chunk::new(value, span)).data)
+        }
+        Argument::Named(name, value) => Argument::Named(
+            Chunk::new(owned_buf.next().unwrap().as_str(), name.span),
+            build_reporter(owned_buf, value),
+        ),
+    })
+}
 
 impl OwnedReporter {
     pub fn new(reporter: Chunk<Reporter>) -> Self {
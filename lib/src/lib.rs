@@ -1,8 +1,10 @@
 pub mod compat;
 pub mod error;
+pub mod gc;
 pub mod instruction;
 pub mod metakeys;
-pub mod module_registry;
 pub mod span;
 pub mod token;
 pub mod value;
+
+pub use foliose_derive::{FolioseType, methods};
@@ -1 +1,3 @@
-pub const TYPE_NAME: &'static str = "__type";
+pub const TYPE_NAME: &str = "__type";
+pub const TO_STRING: &str = "__str";
+pub const CONSTRUCTOR: &str = "__constructor";
@@ -13,6 +13,7 @@ pub enum Token<'a> {
     Comma,
     Period,
     DoublePeriod,
+    Ellipsis,
     Hash,
     Equals,
     Bang,
@@ -34,6 +35,8 @@ pub enum Token<'a> {
     NotEquals,
     DoubleAmpersand,
     DoublePipe,
+    QuestionPeriod,
+    DoubleQuestion,
     Arrow,
 
     ParenOpen,
@@ -67,6 +70,7 @@ impl Token<'_> {
             Token::Comma => "a comma",
             Token::Period => "a period",
             Token::DoublePeriod => "a double period",
+            Token::Ellipsis => "an ellipsis",
             Token::Hash => "a hash",
             Token::Equals => "an equal sign",
             Token::Bang => "an exclamation point",
@@ -88,6 +92,8 @@ impl Token<'_> {
             Token::NotEquals => "an inequal sign",
             Token::DoubleAmpersand => "a double ampersand",
             Token::DoublePipe => "a double pipe",
+            Token::QuestionPeriod => "a question mark period",
+            Token::DoubleQuestion => "a double question mark",
             Token::Arrow => "an arrow",
             Token::ParenOpen => "a open parenthesis",
             Token::ParenClose => "a close parenthesis",
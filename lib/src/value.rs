@@ -1,5 +1,25 @@
-use crate::{error::Result, metakeys, module_registry::ModuleRegistry, span::Span};
-use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+use crate::{
+    compat::{context::Host, function::Signature, user_data::UserData},
+    error::{Error, Result},
+    gc::Edge,
+    metakeys,
+    span::{Chunk, Span},
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Debug, Display},
+    rc::Rc,
+};
+use variadics_please::all_tuples;
+
+#[cfg(feature = "serde")]
+mod serde;
+#[cfg(feature = "serde")]
+pub use self::serde::{from_value, to_tracked_value, to_value};
+#[cfg(test)]
+mod tests;
 
 #[derive(Clone)]
 pub enum Value {
@@ -13,6 +33,7 @@ pub enum Value {
     Array(Vec<Self>),
     Map(HashMap<String, Self>),
     MapRef(Rc<RefCell<dyn MapRef>>),
+    UserData(Rc<UserData>),
 }
 
 impl Debug for Value {
@@ -27,6 +48,7 @@ impl Debug for Value {
             Self::Array(arg0) => f.debug_tuple("Array").field(arg0).finish(),
             Self::Map(arg0) => f.debug_tuple("Map").field(arg0).finish(),
             Self::MapRef(_) => f.debug_tuple("MapRef").finish(),
+            Self::UserData(data) => data.fmt(f),
         }
     }
 }
@@ -43,6 +65,7 @@ impl Value {
             Value::Array(_) => "array",
             Value::Map(_) => "map",
             Value::MapRef(_) => "map",
+            Value::UserData(_) => "userdata",
         }
     }
     pub fn type_of(&self) -> String {
@@ -67,9 +90,230 @@ impl Value {
                     String::from("map")
                 }
             }
+            Value::UserData(data) => String::from(data.type_name()),
             _ => String::from(self.primative_type()),
         }
     }
+    /// Visits the shared maps and functions this value refers to, looking inside arrays
+    /// and owned maps.
+    pub fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        match self {
+            Value::Function(function) => visit(Edge::Function(function)),
+            Value::MapRef(map) => visit(Edge::Map(map)),
+            Value::Array(items) => items.iter().for_each(|item| item.trace(visit)),
+            Value::Map(map) => map.values().for_each(|value| value.trace(visit)),
+            _ => (),
+        }
+    }
+
+    /// Writes the value like [`Display`], but fails if a `__str` override fails, or calls
+    /// itself on the same value. `span` is where the value is being written, and is given
+    /// to overrides.
+    pub fn stringify(&self, span: Span) -> Result<String> {
+        let stringify = Stringify {
+            value: self,
+            writing: RefCell::new(Writing::new(span)),
+        };
+        let str = stringify.to_string();
+        match stringify.writing.into_inner().error {
+            Some(error) => Err(error),
+            None => Ok(str),
+        }
+    }
+
+    /// Writes the value's structure, as it would be written nested in an array: strings
+    /// are quoted, and `__str` overrides aren't used.
+    pub fn structure(&self) -> String {
+        let stringify = Stringify {
+            value: self,
+            writing: RefCell::new(Writing {
+                structural: true,
+                ..Writing::new(Span::default())
+            }),
+        };
+        stringify.to_string()
+    }
+
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>, w: &mut Writing) -> fmt::Result {
+        match self {
+            Value::String(str) => write!(f, "{str:?}"),
+            _ => self.fmt_with(f, w),
+        }
+    }
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, w: &mut Writing) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::String(str) => write!(f, "{str}"),
+            Value::Int(int) => write!(f, "{int}"),
+            Value::Float(float) => write!(f, "{float:?}"),
+            Value::Boolean(bool) => write!(f, "{bool}"),
+            Value::Function(_) => write!(f, "<function>"),
+            Value::UserData(data) => write!(f, "<{}>", data.type_name()),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_nested(f, w)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                let to_string = map.get(metakeys::TO_STRING);
+                // Owned maps are copied into the override, so it is told apart by itself.
+                let key = match to_string {
+                    Some(Value::Function(function)) => Rc::as_ptr(function) as *const (),
+                    _ => std::ptr::null(),
+                };
+                if let Some(str) = self.str_override(to_string.cloned(), key, w) {
+                    return write!(f, "{str}");
+                }
+                fmt_entries(f, map, w)
+            }
+            Value::MapRef(map) => {
+                let ptr = Rc::as_ptr(map) as *const ();
+                if w.seen.contains(&ptr) {
+                    return write!(f, "{{...}}");
+                }
+                // The override is called without the map borrowed, so it can change it.
+                let Ok(to_string) = map
+                    .try_borrow()
+                    .map(|map| map.get(metakeys::TO_STRING).cloned())
+                else {
+                    return write!(f, "{{...}}");
+                };
+                if let Some(str) = self.str_override(to_string, ptr, w) {
+                    return write!(f, "{str}");
+                }
+                let Ok(borrowed) = map.try_borrow() else {
+                    return write!(f, "{{...}}");
+                };
+                let Some(entries) = borrowed.as_hashmap() else {
+                    return write!(f, "<{}>", self.type_of());
+                };
+                w.seen.push(ptr);
+                let result = fmt_entries(f, entries, w);
+                w.seen.pop();
+                result
+            }
+        }
+    }
+
+    /// Resolves a `__str` override. Functions are called with the value itself. If one
+    /// fails, the error is kept and the structural representation is written instead.
+    fn str_override(
+        &self,
+        value: Option<Value>,
+        key: *const (),
+        w: &mut Writing,
+    ) -> Option<String> {
+        if w.structural {
+            return None;
+        }
+        let callable = match value? {
+            Value::String(str) => return Some(str),
+            Value::Function(callable) => callable,
+            _ => return None,
+        };
+        if OVERRIDES.with_borrow(|running| running.contains(&key)) {
+            w.error.get_or_insert(Error::new(
+                "This value's '__str' converts the value itself to a string.",
+                w.span,
+            ));
+            return None;
+        }
+
+        OVERRIDES.with_borrow_mut(|running| running.push(key));
+        let result = callable
+            .call(vec![Chunk::new(self.clone(), w.span)], w.span)
+            .and_then(|value| match value {
+                Value::String(str) => Ok(str),
+                value => value.stringify(w.span),
+            });
+        OVERRIDES.with_borrow_mut(|running| running.pop());
+        result.map_err(|error| w.error.get_or_insert(error)).ok()
+    }
+}
+
+thread_local! {
+    /// The values whose `__str` overrides are running on this thread, so an override that
+    /// converts its own value to a string fails rather than recursing forever. Overrides
+    /// run inside the native call that is writing the value, so this follows the native
+    /// call stack rather than any one engine.
+    static OVERRIDES: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The state of writing one value: the maps that are open, which are written as `{...}`
+/// if they are reached again, and the first error from an override.
+struct Writing {
+    seen: Vec<*const ()>,
+    span: Span,
+    error: Option<Error>,
+    /// Whether the top level is written as if nested, without overrides.
+    structural: bool,
+}
+
+impl Writing {
+    fn new(span: Span) -> Self {
+        Self {
+            seen: Vec::new(),
+            span,
+            error: None,
+            structural: false,
+        }
+    }
+}
+
+/// Writes a value while keeping hold of its [`Writing`], for [`Value::stringify`].
+struct Stringify<'a> {
+    value: &'a Value,
+    writing: RefCell<Writing>,
+}
+
+impl Display for Stringify<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let writing = &mut self.writing.borrow_mut();
+        if writing.structural {
+            self.value.fmt_nested(f, writing)
+        } else {
+            self.value.fmt_with(f, writing)
+        }
+    }
+}
+
+fn fmt_entries(
+    f: &mut fmt::Formatter<'_>,
+    map: &HashMap<String, Value>,
+    w: &mut Writing,
+) -> fmt::Result {
+    if let Some(Value::String(name)) = map.get(metakeys::TYPE_NAME) {
+        write!(f, "{name} ")?;
+    }
+    let mut keys: Vec<&String> = map.keys().filter(|k| !k.starts_with("__")).collect();
+    keys.sort();
+    if keys.is_empty() {
+        return write!(f, "{{}}");
+    }
+
+    write!(f, "{{")?;
+    for (i, key) in keys.into_iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, " {key}: ")?;
+        map[key].fmt_nested(f, w)?;
+    }
+    write!(f, " }}")
+}
+
+/// Strings are written without quotes at the top level, but quoted when nested inside
+/// an array or map. Maps that reference themselves are written as `{...}`. Failing
+/// `__str` overrides are ignored here; use [`Value::stringify`] to see their errors.
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, &mut Writing::new(Span::default()))
+    }
 }
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
@@ -83,23 +327,142 @@ impl PartialEq for Value {
             (Value::Array(items1), Value::Array(items2)) => items1 == items2,
             (Value::Map(map1), Value::Map(map2)) => map1 == map2,
             (Value::MapRef(map1), Value::MapRef(map2)) => Rc::ptr_eq(map1, map2),
+            (Value::UserData(data1), Value::UserData(data2)) => Rc::ptr_eq(data1, data2),
             _ => false,
         }
     }
 }
 
 pub trait Call {
-    fn call(&self, args: Vec<Value>, span: Span) -> Result<Value>;
+    fn call(&self, args: Vec<Chunk<Value>>, span: Span) -> Result<Value>;
+
+    /// The names of the parameters this function accepts, used to bind named arguments.
+    fn parameter_names(&self) -> Option<Vec<&str>> {
+        None
+    }
+    /// The declared parameter and return types, used by the static checker. Functions
+    /// without a signature are treated as dynamic.
+    fn signature(&self) -> Option<Signature> {
+        None
+    }
+    /// Lets an interpreter recognise its own function types behind a `dyn Call`.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+    /// Visits the maps and functions this function holds on to, so that the
+    /// [cycle collector](crate::gc) can see through it.
+    fn trace(&self, _visit: &mut dyn FnMut(Edge)) {}
+    /// Calls the function with both positional and named arguments. By default, named
+    /// arguments are moved into position using [`Call::parameter_names`].
+    fn call_named(
+        &self,
+        args: Vec<Chunk<Value>>,
+        named: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        let args = bind_named(self.parameter_names(), args, named, span)?;
+        self.call(args, span)
+    }
+    /// Calls the function from a running engine, which native functions can reach through
+    /// their [`Context`](crate::compat::context::Context). By default, the host is ignored.
+    fn call_from(
+        &self,
+        _host: &dyn Host,
+        args: Vec<Chunk<Value>>,
+        named: Vec<(Chunk<&str>, Chunk<Value>)>,
+        span: Span,
+    ) -> Result<Value> {
+        self.call_named(args, named, span)
+    }
+}
+
+/// Moves named arguments into position after the positional ones, using the names of the
+/// function's parameters.
+pub fn bind_named(
+    names: Option<Vec<&str>>,
+    mut args: Vec<Chunk<Value>>,
+    named: Vec<(Chunk<&str>, Chunk<Value>)>,
+    span: Span,
+) -> Result<Vec<Chunk<Value>>> {
+    let Some((first, _)) = named.first() else {
+        return Ok(args);
+    };
+    let Some(names) = names else {
+        return Err(Error::new(
+            "This function doesn't accept named arguments.",
+            first.span,
+        ));
+    };
+
+    let positional = args.len();
+    let mut slots = vec![None; names.len().saturating_sub(positional)];
+    for (name, value) in named {
+        let index = names.iter().position(|n| *n == name.data).ok_or_else(|| {
+            Error::new(
+                format!("This function has no parameter named '{}'.", name.data),
+                name.span,
+            )
+        })?;
+        match index.checked_sub(positional).map(|i| &mut slots[i]) {
+            Some(slot @ None) => *slot = Some(value),
+            _ => {
+                return Err(Error::new(
+                    format!("The argument '{}' was given more than once.", name.data),
+                    name.span,
+                ));
+            }
+        }
+    }
+
+    let filled = slots.iter().take_while(|s| s.is_some()).count();
+    if slots[filled..].iter().any(Option::is_some) {
+        return Err(Error::new(
+            format!("Missing argument '{}'.", names[positional + filled]),
+            span,
+        ));
+    }
+    args.extend(slots.into_iter().flatten());
+    Ok(args)
 }
 pub trait MapRef: Debug {
     fn get(&self, name: &str) -> Option<&Value>;
-    fn set(&mut self, name: String, val: Value);
+    /// Errors are reported at the span of the property being set.
+    fn set(&mut self, name: String, val: Value) -> Result<()>;
     fn parent(&self) -> Option<Rc<RefCell<dyn MapRef>>> {
         None
     }
     fn as_hashmap(&self) -> Option<&HashMap<String, Value>> {
         None
     }
+    /// Lets an interpreter recognise its own map types behind a `dyn MapRef`.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+    /// Visits the maps and functions this map holds on to. By default, these are found in
+    /// [`MapRef::as_hashmap`].
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) {
+        if let Some(map) = self.as_hashmap() {
+            map.values().for_each(|value| value.trace(visit));
+        }
+    }
+    /// Drops everything the map holds. Called by the [cycle collector](crate::gc) on maps
+    /// that are only kept alive by a cycle.
+    fn clear(&mut self) {}
+}
+impl MapRef for HashMap<String, Value> {
+    fn get(&self, name: &str) -> Option<&Value> {
+        HashMap::get(self, name)
+    }
+    fn set(&mut self, name: String, val: Value) -> Result<()> {
+        self.insert(name, val);
+        Ok(())
+    }
+    fn as_hashmap(&self) -> Option<&HashMap<String, Value>> {
+        Some(self)
+    }
+    fn clear(&mut self) {
+        HashMap::clear(self);
+    }
 }
 
 impl From<String> for Value {
@@ -112,11 +475,45 @@ impl From<i32> for Value {
         Self::Int(value)
     }
 }
+/// Ints that don't fit in an `i32` can't be converted, the same as with `to_value`.
+macro_rules! impl_wide_int {
+    ($($type: ty),*) => {
+        $(
+            impl TryFrom<$type> for Value {
+                type Error = Error;
+
+                fn try_from(value: $type) -> Result<Self> {
+                    i32::try_from(value).map(Self::Int).map_err(|_| {
+                        Error::new(format!("{value} doesn't fit in an int."), Span::default())
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_wide_int!(i64, u32, usize);
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Self::Float(value.into())
+    }
+}
 impl From<f64> for Value {
     fn from(value: f64) -> Self {
         Self::Float(value)
     }
 }
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+impl From<&Value> for Value {
+    fn from(value: &Value) -> Self {
+        value.clone()
+    }
+}
 impl From<bool> for Value {
     fn from(value: bool) -> Self {
         Self::Boolean(value)
@@ -132,28 +529,31 @@ impl<T: Into<Value>> From<Vec<T>> for Value {
         Self::Array(vec.into_iter().map(Into::into).collect())
     }
 }
-
-impl From<Value> for String {
-    fn from(value: Value) -> Self {
-        match value {
-            Value::String(string) => string,
-            _ => panic!("couldn't convert to string"),
-        }
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
     }
 }
-impl From<Value> for i32 {
-    fn from(value: Value) -> Self {
-        match value {
-            Value::Int(int) => int,
-            _ => panic!("couldn't convert to string"),
-        }
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(map: HashMap<String, T>) -> Self {
+        Self::Map(map.into_iter().map(|(k, v)| (k, v.into())).collect())
     }
 }
-impl From<Value> for Rc<RefCell<dyn MapRef>> {
-    fn from(value: Value) -> Self {
-        match value {
-            Value::MapRef(map) => map,
-            _ => panic!("couldn't convert to map ref"),
-        }
+impl<T: Into<Value>> From<BTreeMap<String, T>> for Value {
+    fn from(map: BTreeMap<String, T>) -> Self {
+        Self::Map(map.into_iter().map(|(k, v)| (k, v.into())).collect())
     }
 }
+
+/// Tuples become arrays.
+macro_rules! impl_tuple {
+    ($(($generic: ident, $var: ident)),*) => {
+        impl<$($generic: Into<Value>),*> From<($($generic,)*)> for Value {
+            fn from(($($var,)*): ($($generic,)*)) -> Self {
+                Self::Array(vec![$($var.into()),*])
+            }
+        }
+    };
+}
+
+all_tuples!(impl_tuple, 1, 16, A, a);
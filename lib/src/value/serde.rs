@@ -0,0 +1,617 @@
+//! Converts between [`Value`]s and Rust types that implement [`Serialize`] and
+//! [`Deserialize`](::serde::Deserialize). Maps and structs become shared maps, like object
+//! literals, so scripts can change them.
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use ::serde::{
+    Serialize,
+    de::{
+        self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+        SeqAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+    ser::{
+        self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+};
+
+use super::Value;
+use crate::{
+    error,
+    gc::{self, Tracker},
+    span::Span,
+    type_error,
+};
+
+/// Converts a Rust value into a script value. The maps it makes aren't known to any
+/// engine's cycle collector; use [`to_tracked_value`] if scripts may make cycles with them.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> error::Result<Value> {
+    value
+        .serialize(Serializer { tracker: None })
+        .map_err(Error::into_error)
+}
+
+/// Converts a Rust value into a script value, tracking the maps it makes with an engine's
+/// cycle collector, such as the one from [`Context::tracker`](crate::compat::context::Context::tracker).
+pub fn to_tracked_value<T: Serialize + ?Sized>(
+    value: &T,
+    tracker: &Tracker,
+) -> error::Result<Value> {
+    value
+        .serialize(Serializer {
+            tracker: Some(tracker),
+        })
+        .map_err(Error::into_error)
+}
+
+/// Converts a script value into a Rust value. Errors say where in the value they happened,
+/// like `servers[1].port`.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> error::Result<T> {
+    T::deserialize(Deserializer {
+        value,
+        path: String::new(),
+    })
+    .map_err(Error::into_error)
+}
+
+#[derive(Debug)]
+struct Error {
+    message: String,
+    /// Where in the value being deserialized the error happened.
+    path: Option<String>,
+}
+
+impl Error {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            path: None,
+        }
+    }
+    /// Records where the error happened, unless a more precise place is already known.
+    fn at(mut self, path: &str) -> Self {
+        if self.path.is_none() && !path.is_empty() {
+            self.path = Some(path.to_string());
+        }
+        self
+    }
+    fn into_error(self) -> error::Error {
+        let message = match self.path {
+            Some(path) => format!("{path}: {}", self.message),
+            None => self.message,
+        };
+        error::Error::new(message, Span::default())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for Error {}
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::new(message.to_string())
+    }
+}
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::new(message.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests;
+
+fn int<T: TryInto<i32> + fmt::Display + Copy>(value: T) -> Result<Value> {
+    value
+        .try_into()
+        .map(Value::Int)
+        .map_err(|_| Error::new(format!("{value} doesn't fit in an int.")))
+}
+
+#[derive(Clone, Copy)]
+struct Serializer<'a> {
+    tracker: Option<&'a Tracker>,
+}
+
+impl Serializer<'_> {
+    fn shared(self, map: HashMap<String, Value>) -> Value {
+        let map = Rc::new(RefCell::new(map));
+        if let Some(tracker) = self.tracker {
+            gc::track(tracker, &map);
+        }
+        Value::MapRef(map)
+    }
+
+    /// Wraps the value of an enum variant as `{ variant: value }`.
+    fn variant(self, variant: &'static str, value: Value) -> Value {
+        self.shared(HashMap::from([(variant.to_string(), value)]))
+    }
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Array<'a>;
+    type SerializeTuple = Array<'a>;
+    type SerializeTupleStruct = Array<'a>;
+    type SerializeTupleVariant = Array<'a>;
+    type SerializeMap = Object<'a>;
+    type SerializeStruct = Object<'a>;
+    type SerializeStructVariant = Object<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Boolean(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        Ok(Value::Int(v.into()))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        Ok(Value::Int(v.into()))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        Ok(Value::Int(v))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        int(v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        Ok(Value::Int(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        Ok(Value::Int(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        int(v)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        int(v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        Ok(Value::Float(v.into()))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        Ok(Value::Float(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(Value::Array(
+            v.iter().map(|&b| Value::Int(b.into())).collect(),
+        ))
+    }
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::String(variant.to_string()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        Ok(self.variant(name, value.serialize(self)?))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Array<'a>> {
+        Ok(Array::new(self, len.unwrap_or(0), None))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Array<'a>> {
+        Ok(Array::new(self, len, None))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Array<'a>> {
+        Ok(Array::new(self, len, None))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Array<'a>> {
+        Ok(Array::new(self, len, Some(variant)))
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Object<'a>> {
+        Ok(Object::new(self, len.unwrap_or(0), None))
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Object<'a>> {
+        Ok(Object::new(self, len, None))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Object<'a>> {
+        Ok(Object::new(self, len, Some(variant)))
+    }
+}
+
+struct Array<'a> {
+    serializer: Serializer<'a>,
+    items: Vec<Value>,
+    variant: Option<&'static str>,
+}
+
+impl<'a> Array<'a> {
+    fn new(serializer: Serializer<'a>, len: usize, variant: Option<&'static str>) -> Self {
+        Self {
+            serializer,
+            items: Vec::with_capacity(len),
+            variant,
+        }
+    }
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(self.serializer)?);
+        Ok(())
+    }
+    fn finish(self) -> Result<Value> {
+        let array = Value::Array(self.items);
+        Ok(match self.variant {
+            Some(name) => self.serializer.variant(name, array),
+            None => array,
+        })
+    }
+}
+
+impl SerializeSeq for Array<'_> {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+impl SerializeTuple for Array<'_> {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+impl SerializeTupleStruct for Array<'_> {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+impl SerializeTupleVariant for Array<'_> {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+struct Object<'a> {
+    serializer: Serializer<'a>,
+    map: HashMap<String, Value>,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl<'a> Object<'a> {
+    fn new(serializer: Serializer<'a>, len: usize, variant: Option<&'static str>) -> Self {
+        Self {
+            serializer,
+            map: HashMap::with_capacity(len),
+            key: None,
+            variant,
+        }
+    }
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        self.map.insert(key, value.serialize(self.serializer)?);
+        Ok(())
+    }
+    fn finish(self) -> Result<Value> {
+        let map = self.serializer.shared(self.map);
+        Ok(match self.variant {
+            Some(name) => self.serializer.variant(name, map),
+            None => map,
+        })
+    }
+}
+
+impl SerializeMap for Object<'_> {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(match key.serialize(self.serializer)? {
+            Value::String(key) => key,
+            key @ (Value::Int(_) | Value::Float(_) | Value::Boolean(_)) => key.to_string(),
+            key => {
+                return Err(Error::new(type_error!(
+                    "a string map key",
+                    key.primative_type()
+                )));
+            }
+        });
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().expect("serialize_key is called first");
+        self.insert(key, value)
+    }
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+impl SerializeStruct for Object<'_> {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+impl SerializeStructVariant for Object<'_> {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+    fn end(self) -> Result<Value> {
+        self.finish()
+    }
+}
+
+/// Deserializes one value, which is at `path` in the value passed to [`from_value`].
+struct Deserializer {
+    value: Value,
+    path: String,
+}
+
+impl Deserializer {
+    /// The entries of a map, leaving out hidden ones like `__type`.
+    fn entries(&self) -> Option<Vec<(String, Value)>> {
+        let entries = |map: &HashMap<String, Value>| {
+            map.iter()
+                .filter(|(key, _)| !key.starts_with("__"))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        };
+        match &self.value {
+            Value::Map(map) => Some(entries(map)),
+            Value::MapRef(map) => map.try_borrow().ok()?.as_hashmap().map(entries),
+            _ => None,
+        }
+    }
+    fn mismatch(&self, expected: &str) -> Error {
+        Error::new(type_error!(expected, self.value.type_of()))
+    }
+
+    fn visit_any<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let Some(entries) = self.entries() {
+            return visitor.visit_map(Entries {
+                entries: entries.into_iter(),
+                value: None,
+                path: self.path,
+            });
+        }
+        match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::String(str) => visitor.visit_string(str),
+            Value::Int(int) => visitor.visit_i32(int),
+            Value::Float(float) => visitor.visit_f64(float),
+            Value::Boolean(bool) => visitor.visit_bool(bool),
+            Value::Array(items) => visitor.visit_seq(Items {
+                items: items.into_iter().enumerate(),
+                path: self.path,
+            }),
+            _ => Err(self.mismatch("data")),
+        }
+    }
+
+    fn visit_enum<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let Value::String(name) = self.value {
+            return visitor.visit_enum(name.into_deserializer());
+        }
+        match self.entries().as_deref() {
+            Some([(name, value)]) => visitor.visit_enum(Variant {
+                name: name.clone(),
+                value: Deserializer {
+                    value: value.clone(),
+                    path: field(&self.path, name),
+                },
+            }),
+            _ => Err(self.mismatch("a variant name or a map with one entry")),
+        }
+    }
+}
+
+fn field(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let path = self.path.clone();
+        self.visit_any(visitor).map_err(|e| e.at(&path))
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let path = self.path.clone();
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+        .map_err(|e| e.at(&path))
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        let path = self.path.clone();
+        visitor.visit_newtype_struct(self).map_err(|e| e.at(&path))
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let path = self.path.clone();
+        self.visit_enum(visitor).map_err(|e| e.at(&path))
+    }
+    /// Ignored values aren't looked at, so they can be anything, even functions.
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier
+    }
+}
+
+struct Items {
+    items: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+    path: String,
+}
+
+impl<'de> SeqAccess<'de> for Items {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        let Some((i, value)) = self.items.next() else {
+            return Ok(None);
+        };
+        seed.deserialize(Deserializer {
+            value,
+            path: format!("{}[{i}]", self.path),
+        })
+        .map(Some)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct Entries {
+    entries: std::vec::IntoIter<(String, Value)>,
+    /// The value of the entry whose key was just deserialized.
+    value: Option<(String, Value)>,
+    path: String,
+}
+
+impl<'de> MapAccess<'de> for Entries {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        let path = field(&self.path, &key);
+        self.value = Some((path, value));
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (path, value) = self.value.take().expect("next_key_seed is called first");
+        seed.deserialize(Deserializer { value, path })
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct Variant {
+    name: String,
+    value: Deserializer,
+}
+
+impl<'de> EnumAccess<'de> for Variant {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Deserializer)> {
+        let name = seed.deserialize(self.name.into_deserializer())?;
+        Ok((name, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.value {
+            Value::Null => Ok(()),
+            _ => Err(self.mismatch("null").at(&self.path)),
+        }
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
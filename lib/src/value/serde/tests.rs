@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::{from_value, to_value};
+use crate::{compat::function::IntoCallable, error::Result, value::Value};
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Server {
+    host: String,
+    port: u16,
+}
+
+fn function() -> Value {
+    (|| -> Result<()> { Ok(()) }).into_callable().into()
+}
+
+fn server(extra: Value) -> Value {
+    Value::Map(HashMap::from([
+        (String::from("host"), Value::from("localhost")),
+        (String::from("port"), Value::Int(8080)),
+        (String::from("on_start"), extra),
+    ]))
+}
+
+#[test]
+fn ignored_fields_can_hold_functions() {
+    let server: Server = from_value(server(function())).unwrap();
+    assert_eq!(
+        server,
+        Server {
+            host: String::from("localhost"),
+            port: 8080,
+        }
+    );
+}
+
+#[test]
+fn functions_in_read_fields_are_still_rejected() {
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Hooks {
+        on_start: String,
+    }
+    let error = from_value::<Hooks>(server(function())).unwrap_err();
+    assert!(format!("{error:?}").contains("on_start:"), "{error:?}");
+}
+
+#[test]
+fn values_round_trip() {
+    let value = to_value(&HashMap::from([("port", 8080)])).unwrap();
+    let map: HashMap<String, u16> = from_value(value).unwrap();
+    assert_eq!(map["port"], 8080);
+}
@@ -0,0 +1,74 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{MapRef, Value};
+use crate::{compat::function::IntoCallable, error::Result, metakeys, span::Span};
+
+fn map_ref(entries: Vec<(&str, Value)>) -> Rc<RefCell<dyn MapRef>> {
+    let map = entries
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect::<HashMap<_, _>>();
+    Rc::new(RefCell::new(map))
+}
+
+#[test]
+fn scalars_are_written_plainly() {
+    assert_eq!(Value::Null.to_string(), "null");
+    assert_eq!(Value::from("text").to_string(), "text");
+    assert_eq!(Value::Int(-3).to_string(), "-3");
+    assert_eq!(Value::Float(2.0).to_string(), "2.0");
+    assert_eq!(Value::Boolean(true).to_string(), "true");
+}
+
+#[test]
+fn nested_strings_are_quoted() {
+    let value = Value::Array(vec![Value::from("a"), Value::Int(1), Value::Array(vec![])]);
+    assert_eq!(value.to_string(), "[\"a\", 1, []]");
+}
+
+#[test]
+fn maps_are_written_with_sorted_keys_and_their_type_name() {
+    let map = Value::MapRef(map_ref(vec![
+        ("y", Value::Int(2)),
+        ("x", Value::from("one")),
+        (metakeys::TYPE_NAME, Value::from("Point")),
+    ]));
+    assert_eq!(map.to_string(), "Point { x: \"one\", y: 2 }");
+    assert_eq!(Value::MapRef(map_ref(vec![])).to_string(), "{}");
+}
+
+#[test]
+fn maps_that_contain_themselves_are_written_once() {
+    let map = map_ref(vec![]);
+    map.borrow_mut()
+        .set("me".to_string(), Value::MapRef(map.clone()))
+        .unwrap();
+    assert_eq!(Value::MapRef(map.clone()).to_string(), "{ me: {...} }");
+    // Break the cycle so the map is freed.
+    map.borrow_mut().set("me".to_string(), Value::Null).unwrap();
+}
+
+#[test]
+fn str_overrides_are_used_at_the_top_level_and_nested() {
+    let named = Value::MapRef(map_ref(vec![(metakeys::TO_STRING, Value::from("named"))]));
+    assert_eq!(named.to_string(), "named");
+    assert_eq!(Value::Array(vec![named.clone()]).to_string(), "[named]");
+    assert_eq!(named.structure(), "{}");
+
+    let describe = (|_: Value| -> Result<String> { Ok(String::from("described")) }).into_callable();
+    let described = Value::MapRef(map_ref(vec![(metakeys::TO_STRING, describe.into())]));
+    assert_eq!(described.stringify(Span::default()).unwrap(), "described");
+}
+
+#[test]
+fn a_str_override_that_writes_its_own_value_fails() {
+    let itself = (|value: Value| value.stringify(Span::default())).into_callable();
+    let value = Value::MapRef(map_ref(vec![(metakeys::TO_STRING, itself.into())]));
+    let error = value.stringify(Span::default()).unwrap_err();
+    assert!(
+        format!("{error:?}").contains("converts the value itself to a string"),
+        "{error:?}"
+    );
+    // Display falls back to the structure.
+    assert_eq!(value.to_string(), "{}");
+}